@@ -0,0 +1,251 @@
+use aws_sdk_s3::{
+    error::{ProvideErrorMetadata, SdkError},
+    primitives::{DateTime, DateTimeFormat},
+    Client,
+};
+use futures::{stream, StreamExt};
+use std::fmt;
+
+use crate::{
+    clients::Clients,
+    scan::{self, ScanLimits},
+};
+
+/// Buckets to fetch metadata for at once
+const METADATA_CONCURRENCY: usize = 16;
+/// Buckets in each ListBuckets page. Without a page size, ListBuckets isn't paginated
+const LIST_BUCKETS_PAGE_SIZE: i32 = 1000;
+/// Tags to show before summarising the rest as a count
+const SHOWN_TAGS: usize = 3;
+
+/// A bucket as shown in the selector. Metadata that couldn't be fetched is `None`
+#[derive(Debug, Clone)]
+pub struct BucketSummary {
+    pub name: String,
+    pub created: Option<DateTime>,
+    pub region: Option<String>,
+    pub versioning: Option<String>,
+    pub tags: Option<Vec<(String, String)>>,
+    /// Only checked for `--inactive-for`
+    pub activity: Option<Activity>,
+    /// Why the bucket can't be deleted, if it can't
+    pub protection: Option<String>,
+}
+
+/// When a bucket's objects were last written
+#[derive(Debug, Clone, Copy)]
+pub struct Activity {
+    /// Newest LastModified of any version or delete marker. `None` for an empty bucket
+    pub last_write: Option<DateTime>,
+    /// Whether listing stopped at the page limit, so a newer write may have been missed
+    pub sampled: bool,
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.last_write, self.sampled) {
+            (None, false) => write!(f, "no objects"),
+            (None, true) => write!(f, "last write unknown"),
+            (Some(last_write), false) => write!(f, "last write {}", format_day(&last_write)),
+            (Some(last_write), true) => {
+                write!(f, "last write {} or later", format_day(&last_write))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BucketSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let created = self
+            .created
+            .map(|created| format_day(&created))
+            .unwrap_or_else(|| "?".to_owned());
+
+        write!(
+            f,
+            "{} (created {}, {}, versioning {}, {})",
+            self.name,
+            created,
+            self.region.as_deref().unwrap_or("unknown region"),
+            self.versioning.as_deref().unwrap_or("unknown"),
+            self.tag_summary()
+        )?;
+
+        if let Some(activity) = &self.activity {
+            write!(f, " ({})", activity)?;
+        }
+
+        if let Some(reason) = &self.protection {
+            write!(f, " [{}]", reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl BucketSummary {
+    fn tag_summary(&self) -> String {
+        let Some(tags) = &self.tags else {
+            return "tags unknown".to_owned();
+        };
+        if tags.is_empty() {
+            return "no tags".to_owned();
+        }
+
+        let mut shown: Vec<_> = tags
+            .iter()
+            .take(SHOWN_TAGS)
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        if tags.len() > SHOWN_TAGS {
+            shown.push(format!("+{} more", tags.len() - SHOWN_TAGS));
+        }
+        format!("tags {}", shown.join(" "))
+    }
+}
+
+/// Filters ListBuckets applies itself, so buckets outside them are never listed
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    pub prefix: Option<String>,
+    /// Lists each region separately. Empty lists every region at once
+    pub regions: Vec<String>,
+}
+
+/// Lists buckets, fetching metadata only for those `keep` accepts by name and creation date. Buckets missing a name
+/// can't be addressed, so they're skipped
+pub async fn list_buckets(
+    clients: &Clients,
+    filter: &ListFilter,
+    keep: impl Fn(&str, Option<&DateTime>) -> bool,
+) -> Result<Vec<BucketSummary>, aws_sdk_s3::Error> {
+    let regions = match filter.regions.is_empty() {
+        true => vec![None],
+        false => filter.regions.iter().map(Some).collect(),
+    };
+
+    let mut buckets = Vec::new();
+    for region in regions {
+        let mut pages = clients
+            .default_client()
+            .list_buckets()
+            .set_prefix(filter.prefix.clone())
+            .set_bucket_region(region.cloned())
+            .into_paginator()
+            .page_size(LIST_BUCKETS_PAGE_SIZE)
+            .send();
+
+        while let Some(page) = pages.next().await {
+            for bucket in page?.buckets() {
+                let Some(name) = bucket.name() else {
+                    continue;
+                };
+                if let Some(region) = bucket.bucket_region() {
+                    clients.remember_region(name, region);
+                }
+                if keep(name, bucket.creation_date()) {
+                    buckets.push((name.to_owned(), bucket.creation_date().copied()));
+                }
+            }
+        }
+    }
+
+    let summaries = stream::iter(buckets)
+        .map(|(name, created)| summarize(clients, name, created))
+        .buffered(METADATA_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(summaries)
+}
+
+async fn summarize(clients: &Clients, name: String, created: Option<DateTime>) -> BucketSummary {
+    let region = clients.bucket_region(&name).await.ok();
+    let client = region.as_deref().map(|region| clients.for_region(region));
+
+    let (versioning, tags) = match &client {
+        Some(client) => {
+            let (versioning, tags) =
+                tokio::join!(bucket_versioning(client, &name), bucket_tags(client, &name));
+            (versioning.ok(), tags.ok())
+        }
+        None => (None, None),
+    };
+
+    BucketSummary {
+        name,
+        created,
+        region,
+        versioning,
+        tags,
+        activity: None,
+        protection: None,
+    }
+}
+
+/// Finds when each unprotected bucket was last written, reading at most `max_pages` pages of versions from each.
+/// Buckets that couldn't be listed are left without activity
+pub async fn fetch_activity(clients: &Clients, buckets: &mut [BucketSummary], max_pages: usize) {
+    let activity: Vec<_> = stream::iter(buckets.iter())
+        .map(|bucket| async move {
+            if bucket.protection.is_some() {
+                return None;
+            }
+            let scan = scan::sample_bucket(
+                clients,
+                &bucket.name,
+                ScanLimits {
+                    max_pages: Some(max_pages),
+                    max_objects: None,
+                },
+            )
+            .await
+            .ok()?;
+            Some(Activity {
+                last_write: scan.newest_last_modified,
+                sampled: scan.sampled,
+            })
+        })
+        .buffered(METADATA_CONCURRENCY)
+        .collect()
+        .await;
+
+    for (bucket, activity) in buckets.iter_mut().zip(activity) {
+        bucket.activity = activity;
+    }
+}
+
+/// `YYYY-MM-DD`
+pub fn format_day(date: &DateTime) -> String {
+    date.fmt(DateTimeFormat::DateTime)
+        .map(|date| date.chars().take(10).collect())
+        .unwrap_or_else(|_| "?".to_owned())
+}
+
+async fn bucket_versioning(client: &Client, name: &str) -> Result<String, aws_sdk_s3::Error> {
+    let response = client.get_bucket_versioning().bucket(name).send().await?;
+
+    let status = match response.status() {
+        Some(status) => status.as_str().to_lowercase(),
+        None => "never enabled".to_owned(),
+    };
+    Ok(status)
+}
+
+async fn bucket_tags(
+    client: &Client,
+    name: &str,
+) -> Result<Vec<(String, String)>, aws_sdk_s3::Error> {
+    let response = match client.get_bucket_tagging().bucket(name).send().await {
+        Ok(response) => response,
+        Err(SdkError::ServiceError(err)) if err.err().code() == Some("NoSuchTagSet") => {
+            return Ok(Vec::new())
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let tags = response
+        .tag_set()
+        .iter()
+        .map(|tag| (tag.key().to_owned(), tag.value().to_owned()))
+        .collect();
+    Ok(tags)
+}
@@ -0,0 +1,422 @@
+use aws_sdk_s3::primitives::DateTime;
+use clap::{Parser, Subcommand};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, IsTerminal, Read},
+    num::NonZeroUsize,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    buckets::{BucketSummary, ListFilter},
+    protection::{MatchMode, ProtectionRule, RuleConfig},
+};
+
+#[derive(Parser)]
+#[command(version, about = "Bang, and the bucket is gone")]
+pub struct Args {
+    /// Picks buckets to delete interactively when not given
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Don't ask for confirmation. Required when running without a TTY
+    #[arg(long, short, global = true)]
+    pub yes: bool,
+
+    /// Make the operator type each bucket's name before it's deleted
+    #[arg(long, global = true)]
+    pub type_names: bool,
+
+    /// Most buckets one run may delete. Defaults to 5
+    #[arg(long, value_name = "COUNT", global = true)]
+    pub max_buckets: Option<usize>,
+
+    /// Most object versions and delete markers one run may delete, across every bucket
+    #[arg(long, value_name = "COUNT", global = true)]
+    pub max_objects: Option<usize>,
+
+    /// Allow selecting more than 5 buckets, and raising --max-buckets or --max-objects above config
+    #[arg(long, global = true)]
+    pub i_know_what_im_doing: bool,
+
+    /// AWS profile to use. Asks which one when there are several and AWS_PROFILE isn't set
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Region to send requests to, when a bucket's own region isn't needed
+    #[arg(long, global = true)]
+    pub region: Option<String>,
+
+    /// Send requests to an S3-compatible store, such as MinIO or LocalStack
+    #[arg(long, value_name = "URL", global = true)]
+    pub endpoint_url: Option<String>,
+
+    /// Address buckets in the URL path rather than the hostname
+    #[arg(long, global = true)]
+    pub force_path_style: bool,
+
+    /// Most pages of listings to read from each bucket for the impact report. Larger buckets are sampled
+    #[arg(long, value_name = "PAGES", default_value = "100", global = true)]
+    pub scan_pages: NonZeroUsize,
+
+    /// Number of DeleteObjects requests to run in parallel for each bucket
+    #[arg(long, default_value = "8", global = true)]
+    pub concurrency: NonZeroUsize,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Choose buckets and delete them
+    Delete {
+        #[command(flatten)]
+        selection: Selection,
+
+        /// List what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Choose buckets and write a deletion plan for review, without deleting anything
+    Plan {
+        /// Where to write the plan
+        #[arg(long, short, value_name = "PATH")]
+        output: PathBuf,
+
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Delete the buckets in a plan, refusing if any changed since it was made
+    Apply {
+        /// Plan written by `s3-bang plan`
+        plan: PathBuf,
+    },
+}
+
+#[derive(clap::Args, Default)]
+pub struct Selection {
+    /// Buckets to delete. Skips the interactive selector when given
+    pub buckets: Vec<String>,
+
+    /// Read bucket names from a file, one per line. Use `-` for stdin
+    #[arg(long, value_name = "PATH")]
+    pub from_file: Option<PathBuf>,
+
+    /// Only list buckets in these regions
+    #[arg(long, value_name = "REGION", value_delimiter = ',')]
+    pub in_region: Vec<String>,
+
+    /// Only list buckets whose names match. A glob, or a regex when prefixed with `regex:`
+    #[arg(long = "match", value_name = "PATTERN", value_parser = parse_pattern)]
+    pub matching: Vec<ProtectionRule>,
+
+    /// Leave out buckets whose names match. Takes the same patterns as --match
+    #[arg(long, value_name = "PATTERN", value_parser = parse_pattern)]
+    pub exclude: Vec<ProtectionRule>,
+
+    /// Only list buckets created at least this long ago, such as 12h, 30d or 2w
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub older_than: Option<Duration>,
+
+    /// Show when each bucket was last written, protecting buckets written within this long, such as 90d
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub inactive_for: Option<Duration>,
+}
+
+impl Default for Command {
+    fn default() -> Command {
+        Command::Delete {
+            selection: Selection::default(),
+            dry_run: false,
+        }
+    }
+}
+
+impl Command {
+    /// Whether this run can delete anything, and so needs confirming
+    pub fn deletes(&self) -> bool {
+        match self {
+            Command::Delete { dry_run, .. } => !dry_run,
+            Command::Plan { .. } => false,
+            Command::Apply { .. } => true,
+        }
+    }
+}
+
+impl Selection {
+    /// Bucket names given on the command line or through `--from-file`
+    pub fn requested_buckets(&self) -> io::Result<Vec<String>> {
+        let mut buckets = self.buckets.clone();
+
+        if let Some(path) = &self.from_file {
+            let contents = if path.as_os_str() == "-" {
+                let mut contents = String::new();
+                io::stdin().read_to_string(&mut contents)?;
+                contents
+            } else {
+                fs::read_to_string(path)?
+            };
+
+            buckets.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_owned),
+            );
+        }
+
+        let mut seen = HashSet::new();
+        buckets.retain(|bucket| seen.insert(bucket.clone()));
+        Ok(buckets)
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        self.buckets.is_empty() && self.from_file.is_none()
+    }
+
+    /// Whether any of `--in-region`, `--match`, `--exclude`, `--older-than` or `--inactive-for` were given
+    pub fn has_filters(&self) -> bool {
+        !self.in_region.is_empty()
+            || !self.matching.is_empty()
+            || !self.exclude.is_empty()
+            || self.older_than.is_some()
+            || self.inactive_for.is_some()
+    }
+
+    /// The filters ListBuckets can apply itself: the regions, and the longest prefix every `--match` pattern shares
+    pub fn list_filter(&self) -> ListFilter {
+        let mut prefixes = self.matching.iter().map(ProtectionRule::literal_prefix);
+        let first = prefixes.next().flatten();
+        let prefix = prefixes.fold(first, |shared, prefix| {
+            let (shared, prefix) = (shared?, prefix?);
+            let length = shared
+                .char_indices()
+                .zip(prefix.chars())
+                .take_while(|((_, a), b)| a == b)
+                .last()
+                .map_or(0, |((index, a), _)| index + a.len_utf8());
+            Some(&shared[..length]).filter(|shared| !shared.is_empty())
+        });
+
+        ListFilter {
+            prefix: prefix.map(str::to_owned),
+            regions: self.in_region.clone(),
+        }
+    }
+
+    /// Whether a bucket passes every filter. Buckets whose region or creation date is unknown fail the filters
+    /// needing them
+    pub fn matches(&self, bucket: &BucketSummary) -> bool {
+        let in_region = self.in_region.is_empty()
+            || bucket
+                .region
+                .as_ref()
+                .is_some_and(|region| self.in_region.contains(region));
+
+        in_region && self.matches_listing(&bucket.name, bucket.created.as_ref())
+    }
+
+    /// Whether a bucket passes the filters that only need what ListBuckets returns
+    pub fn matches_listing(&self, name: &str, created: Option<&DateTime>) -> bool {
+        let matching =
+            self.matching.is_empty() || self.matching.iter().any(|rule| rule.matches(name));
+        let excluded = self.exclude.iter().any(|rule| rule.matches(name));
+        let old_enough = self
+            .older_than
+            .is_none_or(|age| created.is_some_and(|created| is_older_than(created.secs(), age)));
+
+        matching && !excluded && old_enough
+    }
+}
+
+/// Whether a time, in seconds since the Unix epoch, is at least `age` ago
+pub fn is_older_than(secs: i64, age: Duration) -> bool {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    now.saturating_sub(secs) >= age.as_secs() as i64
+}
+
+fn parse_pattern(pattern: &str) -> Result<ProtectionRule, String> {
+    let (mode, pattern) = match pattern.strip_prefix("regex:") {
+        Some(pattern) => (MatchMode::Regex, pattern),
+        None => (MatchMode::Glob, pattern),
+    };
+
+    ProtectionRule::new(RuleConfig {
+        pattern: pattern.to_owned(),
+        mode,
+        case_insensitive: false,
+    })
+}
+
+/// Parses ages such as `12h`, `30d` or `2w`
+fn parse_age(age: &str) -> Result<Duration, String> {
+    let unit = age
+        .chars()
+        .last()
+        .ok_or_else(|| "Expected an age such as 30d".to_owned())?;
+    let seconds = match unit {
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit {:?}. Use h, d or w", unit)),
+    };
+
+    let count: u64 = age[..age.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| format!("Expected a number before {:?}", unit))?;
+    count
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Age {} is too large", age))
+}
+
+/// Formats an age the way `parse_age` reads it, in the largest whole unit
+pub fn format_age(age: Duration) -> String {
+    let hours = age.as_secs() / (60 * 60);
+    match hours {
+        _ if hours > 0 && hours.is_multiple_of(7 * 24) => format!("{}w", hours / (7 * 24)),
+        _ if hours > 0 && hours.is_multiple_of(24) => format!("{}d", hours / 24),
+        _ => format!("{}h", hours),
+    }
+}
+
+/// Prompts need both a terminal to draw on and one to read answers from
+pub fn has_tty() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(args).unwrap()
+    }
+
+    fn plan_path(args: &Args) -> Option<&str> {
+        match &args.command {
+            Some(Command::Apply { plan }) => plan.to_str(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn global_flag_before_apply_runs_apply() {
+        let args = parse(&["s3-bang", "--profile", "prod", "apply", "plan.json"]);
+        assert_eq!(plan_path(&args), Some("plan.json"));
+        assert_eq!(args.profile.as_deref(), Some("prod"));
+
+        let args = parse(&["s3-bang", "--yes", "apply", "plan.json"]);
+        assert_eq!(plan_path(&args), Some("plan.json"));
+        assert!(args.yes);
+    }
+
+    #[test]
+    fn global_flag_after_apply_runs_apply() {
+        let args = parse(&["s3-bang", "apply", "plan.json", "--profile", "prod"]);
+        assert_eq!(plan_path(&args), Some("plan.json"));
+        assert_eq!(args.profile.as_deref(), Some("prod"));
+
+        let args = parse(&["s3-bang", "apply", "--yes", "plan.json"]);
+        assert_eq!(plan_path(&args), Some("plan.json"));
+        assert!(args.yes);
+    }
+
+    #[test]
+    fn subcommand_names_are_not_bucket_names() {
+        assert!(Args::try_parse_from(["s3-bang", "--yes", "apply"]).is_err());
+        assert!(Args::try_parse_from(["s3-bang", "old-bucket"]).is_err());
+
+        let args = parse(&["s3-bang", "--yes", "delete", "apply", "plan"]);
+        match args.command {
+            Some(Command::Delete { selection, .. }) => {
+                assert_eq!(selection.buckets, ["apply", "plan"])
+            }
+            _ => panic!("expected delete"),
+        }
+    }
+
+    fn list_prefix(patterns: &[&str]) -> Option<String> {
+        let selection = Selection {
+            matching: patterns
+                .iter()
+                .map(|pattern| parse_pattern(pattern).unwrap())
+                .collect(),
+            ..Selection::default()
+        };
+        selection.list_filter().prefix
+    }
+
+    #[test]
+    fn globs_share_their_common_prefix() {
+        assert_eq!(list_prefix(&["ci-*"]).as_deref(), Some("ci-"));
+        assert_eq!(
+            list_prefix(&["ci-artifacts-*", "ci-logs-*", "ci-a?"]).as_deref(),
+            Some("ci-")
+        );
+        assert_eq!(list_prefix(&["café-*", "cafè-*"]).as_deref(), Some("caf"));
+        assert_eq!(list_prefix(&[]), None);
+    }
+
+    #[test]
+    fn globs_without_a_common_prefix_list_everything() {
+        assert_eq!(list_prefix(&["ci-*", "build-*"]), None);
+        assert_eq!(list_prefix(&["ci-*", "*-logs"]), None);
+        assert_eq!(list_prefix(&["*-logs", "ci-*"]), None);
+    }
+
+    #[test]
+    fn regexes_list_everything() {
+        assert_eq!(list_prefix(&["regex:^ci-"]), None);
+        assert_eq!(list_prefix(&["ci-*", "regex:^ci-"]), None);
+        assert_eq!(list_prefix(&["regex:^ci-", "ci-*"]), None);
+    }
+
+    #[test]
+    fn list_filter_keeps_regions() {
+        let selection = Selection {
+            in_region: vec!["eu-west-1".into(), "us-east-1".into()],
+            ..Selection::default()
+        };
+        let filter = selection.list_filter();
+        assert_eq!(filter.prefix, None);
+        assert_eq!(filter.regions, ["eu-west-1", "us-east-1"]);
+    }
+
+    #[test]
+    fn ages_round_trip() {
+        for age in ["0h", "12h", "36h", "1d", "30d", "1w", "52w"] {
+            assert_eq!(format_age(parse_age(age).unwrap()), age);
+        }
+        assert_eq!(format_age(parse_age("14d").unwrap()), "2w");
+        assert_eq!(format_age(parse_age("48h").unwrap()), "2d");
+    }
+
+    #[test]
+    fn bad_ages_are_rejected() {
+        for age in ["", "30", "30m", "30 d", "-1d", "d", "1.5w", "30дн"] {
+            assert!(parse_age(age).is_err(), "{:?}", age);
+        }
+    }
+
+    #[test]
+    fn huge_ages_are_rejected() {
+        assert!(parse_age(&format!("{}w", u64::MAX / 60)).is_err());
+        assert!(parse_age(&format!("{}h", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn no_subcommand_deletes_interactively() {
+        let mut args = parse(&["s3-bang", "--profile", "prod"]);
+        let command = args.command.take().unwrap_or_default();
+        assert!(command.deletes());
+        match command {
+            Command::Delete { selection, .. } => assert!(selection.is_interactive()),
+            _ => panic!("expected delete"),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use aws_config::SdkConfig;
+use aws_sdk_s3::{config::Region, Client};
+use std::{collections::HashMap, sync::Mutex};
+
+/// Where ListBuckets says a bucket with no location constraint lives
+const DEFAULT_BUCKET_REGION: &str = "us-east-1";
+
+/// S3 clients for every region a bucket has been found in, so requests for a bucket go to its own region
+pub struct Clients {
+    sdk_config: SdkConfig,
+    /// S3-compatible store to send requests to instead of AWS
+    endpoint_url: Option<String>,
+    force_path_style: bool,
+    default: Client,
+    by_region: Mutex<HashMap<String, Client>>,
+    bucket_regions: Mutex<HashMap<String, String>>,
+}
+
+impl Clients {
+    pub fn new(
+        sdk_config: SdkConfig,
+        endpoint_url: Option<String>,
+        force_path_style: bool,
+    ) -> Clients {
+        let default = build_client(&sdk_config, endpoint_url.as_deref(), force_path_style, None);
+
+        Clients {
+            sdk_config,
+            endpoint_url,
+            force_path_style,
+            default,
+            by_region: Mutex::new(HashMap::new()),
+            bucket_regions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Client for the configured region, for requests that aren't about one bucket
+    pub fn default_client(&self) -> &Client {
+        &self.default
+    }
+
+    pub async fn for_bucket(&self, bucket: &str) -> Result<Client, aws_sdk_s3::Error> {
+        let region = self.bucket_region(bucket).await?;
+        Ok(self.for_region(&region))
+    }
+
+    pub async fn bucket_region(&self, bucket: &str) -> Result<String, aws_sdk_s3::Error> {
+        if let Some(region) = self.bucket_regions.lock().unwrap().get(bucket) {
+            return Ok(region.to_owned());
+        }
+
+        // S3-compatible stores rarely care about regions, but AWS answers location lookups from us-east-1
+        let lookup = match self.endpoint_url {
+            Some(_) => self.default.clone(),
+            None => self.for_region(DEFAULT_BUCKET_REGION),
+        };
+        let location = lookup.get_bucket_location().bucket(bucket).send().await?;
+
+        let region = match location
+            .location_constraint()
+            .map(|constraint| constraint.as_str())
+        {
+            None | Some("") => DEFAULT_BUCKET_REGION,
+            Some("EU") => "eu-west-1",
+            Some(region) => region,
+        }
+        .to_owned();
+
+        self.remember_region(bucket, &region);
+        Ok(region)
+    }
+
+    /// Records a region already known, such as from ListBuckets, saving a GetBucketLocation
+    pub fn remember_region(&self, bucket: &str, region: &str) {
+        self.bucket_regions
+            .lock()
+            .unwrap()
+            .insert(bucket.to_owned(), region.to_owned());
+    }
+
+    /// CloudWatch client for the region a bucket's storage metrics are kept in. S3-compatible stores have none
+    pub async fn cloudwatch_for_bucket(&self, bucket: &str) -> Option<aws_sdk_cloudwatch::Client> {
+        if self.endpoint_url.is_some() {
+            return None;
+        }
+
+        let region = self.bucket_region(bucket).await.ok()?;
+        let config = aws_sdk_cloudwatch::config::Builder::from(&self.sdk_config)
+            .region(Region::new(region))
+            .build();
+        Some(aws_sdk_cloudwatch::Client::from_conf(config))
+    }
+
+    pub fn for_region(&self, region: &str) -> Client {
+        self.by_region
+            .lock()
+            .unwrap()
+            .entry(region.to_owned())
+            .or_insert_with(|| {
+                build_client(
+                    &self.sdk_config,
+                    self.endpoint_url.as_deref(),
+                    self.force_path_style,
+                    Some(region),
+                )
+            })
+            .clone()
+    }
+}
+
+fn build_client(
+    sdk_config: &SdkConfig,
+    endpoint_url: Option<&str>,
+    force_path_style: bool,
+    region: Option<&str>,
+) -> Client {
+    let mut config =
+        aws_sdk_s3::config::Builder::from(sdk_config).force_path_style(force_path_style);
+    if let Some(endpoint_url) = endpoint_url {
+        config = config.endpoint_url(endpoint_url);
+    }
+    if let Some(region) = region {
+        config = config.region(Region::new(region.to_owned()));
+    }
+
+    Client::from_conf(config.build())
+}
@@ -0,0 +1,429 @@
+use serde::Deserialize;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::protection::{ProtectionRule, RuleConfig, TagRule};
+
+/// Buckets one run may delete unless raised with `--i-know-what-im-doing`
+pub const MAX_BUCKETS: usize = 5;
+const PROTECTED_BUCKET_NAMES: &[&str] = &["backup", "do-not-delete", "console"];
+const LOCAL_CONFIG_FILE: &str = ".s3bang.toml";
+
+/// Settings merged from every config file found, lowest precedence first
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub protection_rules: Vec<ProtectionRule>,
+    pub tag_rules: Vec<TagRule>,
+    pub confirmation: Confirmation,
+    pub max_buckets: usize,
+    /// Object versions and delete markers one run may delete, across every bucket
+    pub max_objects: Option<usize>,
+    /// Accounts s3-bang may run against. `None` allows any account not denied
+    pub allowed_accounts: Option<Vec<String>>,
+    pub denied_accounts: Vec<String>,
+    /// Send requests to an S3-compatible store instead of AWS
+    pub endpoint_url: Option<String>,
+    pub force_path_style: bool,
+    /// Allows selecting more than `MAX_BUCKETS` buckets. Only set by `--i-know-what-im-doing`
+    pub i_know_what_im_doing: bool,
+}
+
+/// When the operator has to type a bucket's name before it's deleted
+#[derive(Debug, Clone, Default)]
+pub struct Confirmation {
+    pub type_names: bool,
+    pub above_objects: Option<usize>,
+    pub above_bytes: Option<u64>,
+}
+
+impl Confirmation {
+    /// Whether deciding needs a bucket's size
+    pub fn has_thresholds(&self) -> bool {
+        self.above_objects.is_some() || self.above_bytes.is_some()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    protection: ProtectionFile,
+    confirmation: ConfirmationFile,
+    limits: LimitsFile,
+    allowed_accounts: Option<Vec<String>>,
+    denied_accounts: Vec<String>,
+    s3: S3File,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProtectionFile {
+    include_defaults: Option<bool>,
+    names: Vec<String>,
+    rules: Vec<RuleConfig>,
+    tags: Vec<TagRule>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfirmationFile {
+    type_names: Option<bool>,
+    type_names_above_objects: Option<usize>,
+    type_names_above_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsFile {
+    max_buckets: Option<usize>,
+    max_objects: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct S3File {
+    endpoint_url: Option<String>,
+    force_path_style: Option<bool>,
+}
+
+/// One config file that was found and parsed
+struct Layer {
+    path: PathBuf,
+    /// Found in the current directory or a parent rather than a system or user location, so it may have come with a
+    /// cloned repository and can only tighten safety settings
+    repo_local: bool,
+    file: ConfigFile,
+}
+
+impl Config {
+    /// Reads the system, user and repo-local config files, in that order
+    pub fn load() -> Result<Config, String> {
+        let mut layers = Vec::new();
+        for (path, repo_local) in config_paths() {
+            if let Some(file) = read_config_file(&path)? {
+                layers.push(Layer {
+                    path,
+                    repo_local,
+                    file,
+                });
+            }
+        }
+
+        Config::from_layers(layers)
+    }
+
+    /// Merges config files, lowest precedence first. Safety settings can only be tightened: protected names from
+    /// every file apply, the built-in ones stay unless no file asks to keep them and a system or user file drops
+    /// them, and the lowest bucket and object limits apply. Later files win for everything else
+    fn from_layers(layers: Vec<Layer>) -> Result<Config, String> {
+        let mut include_defaults: Option<bool> = None;
+        let mut protection_rules = Vec::new();
+        let mut tag_rules = Vec::new();
+        let mut confirmation = Confirmation::default();
+        let mut max_buckets = None;
+        let mut max_objects = None;
+        let mut allowed_accounts: Option<Vec<String>> = None;
+        let mut denied_accounts = Vec::new();
+        let mut endpoint_url = None;
+        let mut force_path_style = false;
+
+        for Layer {
+            path,
+            repo_local,
+            file,
+        } in layers
+        {
+            match file.protection.include_defaults {
+                Some(false) if repo_local => {
+                    return Err(format!(
+                        "{} can't set include_defaults = false. Set it in system or user config",
+                        path.display()
+                    ))
+                }
+                // Any file keeping the built-in rules keeps them
+                Some(include) => {
+                    include_defaults = Some(include_defaults.unwrap_or(false) || include)
+                }
+                None => {}
+            }
+            protection_rules.extend(
+                file.protection
+                    .names
+                    .iter()
+                    .map(|name| ProtectionRule::contains(name)),
+            );
+            for rule in file.protection.rules {
+                let pattern = rule.pattern.clone();
+                protection_rules.push(ProtectionRule::new(rule).map_err(|err| {
+                    format!("Invalid rule {:?} in {}: {}", pattern, path.display(), err)
+                })?);
+            }
+            tag_rules.extend(file.protection.tags);
+
+            // Any file can ask for typed names, and the lowest threshold applies
+            let typed = file.confirmation;
+            confirmation.type_names |= typed.type_names.unwrap_or(false);
+            confirmation.above_objects =
+                lowest(confirmation.above_objects, typed.type_names_above_objects);
+            confirmation.above_bytes =
+                lowest(confirmation.above_bytes, typed.type_names_above_bytes);
+
+            max_buckets = lowest(max_buckets, file.limits.max_buckets);
+            max_objects = lowest(max_objects, file.limits.max_objects);
+
+            // Every file's allow list must permit an account, so a later file can't widen an earlier one
+            if let Some(allowed) = file.allowed_accounts {
+                allowed_accounts = Some(match allowed_accounts {
+                    Some(current) => current
+                        .into_iter()
+                        .filter(|account| allowed.contains(account))
+                        .collect(),
+                    None => allowed,
+                });
+            }
+            denied_accounts.extend(file.denied_accounts);
+
+            if repo_local && file.s3.endpoint_url.is_some() {
+                return Err(format!(
+                    "{} can't set endpoint_url. Pass --endpoint-url or set it in system or user config",
+                    path.display()
+                ));
+            }
+            endpoint_url = file.s3.endpoint_url.or(endpoint_url);
+            force_path_style = file.s3.force_path_style.unwrap_or(force_path_style);
+        }
+
+        if include_defaults.unwrap_or(true) {
+            protection_rules.extend(
+                PROTECTED_BUCKET_NAMES
+                    .iter()
+                    .map(|name| ProtectionRule::contains(name)),
+            );
+        }
+
+        Ok(Config {
+            protection_rules,
+            tag_rules,
+            confirmation,
+            max_buckets: max_buckets.unwrap_or(MAX_BUCKETS),
+            max_objects,
+            allowed_accounts,
+            denied_accounts,
+            endpoint_url,
+            force_path_style,
+            i_know_what_im_doing: false,
+        })
+    }
+
+    pub fn check_account(&self, account_id: &str) -> Result<(), String> {
+        if self
+            .denied_accounts
+            .iter()
+            .any(|denied| denied == account_id)
+        {
+            return Err(format!("Account {} is denied by config", account_id));
+        }
+
+        match &self.allowed_accounts {
+            Some(allowed) if !allowed.iter().any(|account| account == account_id) => Err(format!(
+                "Account {} is not in the allowed accounts in config",
+                account_id
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// First rule protecting a bucket name, if any
+    pub fn protecting_rule(&self, name: &str) -> Option<&ProtectionRule> {
+        self.protection_rules.iter().find(|rule| rule.matches(name))
+    }
+
+    /// First rule protecting a bucket with these tags, and the tag it matched
+    pub fn protecting_tag<'a>(
+        &self,
+        tags: &'a [(String, String)],
+    ) -> Option<(&TagRule, &'a (String, String))> {
+        self.tag_rules.iter().find_map(|rule| {
+            tags.iter()
+                .find(|(key, value)| rule.matches(key, value))
+                .map(|tag| (rule, tag))
+        })
+    }
+}
+
+/// The lower of two optional limits, where `None` means no limit
+fn lowest<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<Option<ConfigFile>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Error reading {}: {}", path.display(), err)),
+    };
+
+    toml::from_str(&contents)
+        .map(Some)
+        .map_err(|err| format!("Error parsing {}: {}", path.display(), err))
+}
+
+/// Config files to read, lowest precedence first, and whether each is repo-local
+fn config_paths() -> Vec<(PathBuf, bool)> {
+    let mut paths = vec![(PathBuf::from("/etc/s3-bang/config.toml"), false)];
+
+    if let Some(dir) = dirs::config_dir() {
+        paths.push((dir.join("s3-bang").join("config.toml"), false));
+    }
+
+    // The closest .s3bang.toml in the current directory or any parent
+    if let Ok(cwd) = env::current_dir() {
+        if let Some(local) = cwd
+            .ancestors()
+            .map(|dir| dir.join(LOCAL_CONFIG_FILE))
+            .find(|path| path.is_file())
+        {
+            paths.push((local, true));
+        }
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(contents: &str, repo_local: bool) -> Layer {
+        Layer {
+            path: PathBuf::from(match repo_local {
+                true => LOCAL_CONFIG_FILE,
+                false => "config.toml",
+            }),
+            repo_local,
+            file: toml::from_str(contents).unwrap(),
+        }
+    }
+
+    fn protects(config: &Config, name: &str) -> bool {
+        config.protecting_rule(name).is_some()
+    }
+
+    #[test]
+    fn built_in_names_are_protected_by_default() {
+        let config = Config::from_layers(Vec::new()).unwrap();
+        assert!(protects(&config, "nightly-backup"));
+        assert!(!protects(&config, "scratch"));
+    }
+
+    #[test]
+    fn user_config_can_drop_built_in_names() {
+        let config =
+            Config::from_layers(vec![layer("[protection]\ninclude_defaults = false", false)])
+                .unwrap();
+        assert!(!protects(&config, "nightly-backup"));
+    }
+
+    #[test]
+    fn any_layer_keeping_built_in_names_wins() {
+        let config = Config::from_layers(vec![
+            layer("[protection]\ninclude_defaults = true", false),
+            layer("[protection]\ninclude_defaults = false", false),
+        ])
+        .unwrap();
+        assert!(protects(&config, "nightly-backup"));
+    }
+
+    #[test]
+    fn repo_local_config_cant_drop_built_in_names() {
+        let result =
+            Config::from_layers(vec![layer("[protection]\ninclude_defaults = false", true)]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn later_layers_cant_loosen_typed_confirmation() {
+        let config = Config::from_layers(vec![
+            layer(
+                "[confirmation]\ntype_names = true\ntype_names_above_objects = 100",
+                false,
+            ),
+            layer(
+                "[confirmation]\ntype_names = false\ntype_names_above_objects = 5000",
+                true,
+            ),
+        ])
+        .unwrap();
+        assert!(config.confirmation.type_names);
+        assert_eq!(config.confirmation.above_objects, Some(100));
+    }
+
+    #[test]
+    fn lowest_bucket_limit_applies() {
+        let config = Config::from_layers(vec![
+            layer("[limits]\nmax_buckets = 2", false),
+            layer("[limits]\nmax_buckets = 5", true),
+        ])
+        .unwrap();
+        assert_eq!(config.max_buckets, 2);
+
+        let config = Config::from_layers(vec![layer("[limits]\nmax_buckets = 40", false)]).unwrap();
+        assert_eq!(config.max_buckets, 40);
+
+        let config = Config::from_layers(Vec::new()).unwrap();
+        assert_eq!(config.max_buckets, MAX_BUCKETS);
+    }
+
+    #[test]
+    fn lowest_object_limit_applies() {
+        let config = Config::from_layers(vec![
+            layer("[limits]\nmax_objects = 1000", false),
+            layer("[limits]\nmax_objects = 50000", true),
+        ])
+        .unwrap();
+        assert_eq!(config.max_objects, Some(1000));
+    }
+
+    #[test]
+    fn allow_lists_narrow_across_layers() {
+        let config = Config::from_layers(vec![
+            layer(
+                "allowed_accounts = [\"111111111111\", \"222222222222\"]\ndenied_accounts = [\"333333333333\"]",
+                false,
+            ),
+            layer(
+                "allowed_accounts = [\"222222222222\", \"333333333333\"]\ndenied_accounts = [\"444444444444\"]",
+                true,
+            ),
+        ])
+        .unwrap();
+        assert!(config.check_account("111111111111").is_err());
+        assert!(config.check_account("222222222222").is_ok());
+        assert!(config.check_account("333333333333").is_err());
+        assert!(config.check_account("444444444444").is_err());
+        assert!(config.check_account("unknown").is_err());
+    }
+
+    #[test]
+    fn repo_local_config_cant_set_endpoint() {
+        let result = Config::from_layers(vec![layer(
+            "[s3]\nendpoint_url = \"http://attacker.example\"",
+            true,
+        )]);
+        assert!(result.is_err());
+
+        let config = Config::from_layers(vec![layer(
+            "[s3]\nendpoint_url = \"http://localhost:9000\"",
+            false,
+        )])
+        .unwrap();
+        assert_eq!(
+            config.endpoint_url.as_deref(),
+            Some("http://localhost:9000")
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use aws_config::SdkConfig;
+use std::fmt;
+
+/// Who the configured credentials act as, and where
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub account_id: String,
+    pub alias: Option<String>,
+    pub arn: String,
+    pub region: String,
+}
+
+impl Identity {
+    /// Stand-in for stores without STS, such as most S3-compatible ones
+    pub fn unknown(config: &SdkConfig) -> Identity {
+        Identity {
+            account_id: "unknown".into(),
+            alias: None,
+            arn: "unknown principal".into(),
+            region: region(config),
+        }
+    }
+
+    /// Account ID, followed by its alias when there is one
+    pub fn account(&self) -> String {
+        match &self.alias {
+            Some(alias) => format!("{} ({})", self.account_id, alias),
+            None => self.account_id.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "account {} as {} in {}",
+            self.account(),
+            self.arn,
+            self.region
+        )
+    }
+}
+
+pub async fn caller_identity(config: &SdkConfig) -> Result<Identity, aws_sdk_sts::Error> {
+    let identity = aws_sdk_sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await?;
+
+    Ok(Identity {
+        account_id: identity.account().unwrap_or_default().to_owned(),
+        alias: account_alias(config).await,
+        arn: identity.arn().unwrap_or_default().to_owned(),
+        region: region(config),
+    })
+}
+
+fn region(config: &SdkConfig) -> String {
+    config
+        .region()
+        .map(|region| region.to_string())
+        .unwrap_or_default()
+}
+
+/// Not every principal may list aliases, and not every stand-in implements it, so this is best effort
+async fn account_alias(config: &SdkConfig) -> Option<String> {
+    let response = aws_sdk_iam::Client::new(config)
+        .list_account_aliases()
+        .send()
+        .await
+        .ok()?;
+
+    response.account_aliases().first().cloned()
+}
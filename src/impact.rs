@@ -0,0 +1,176 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    buckets::format_day,
+    scan::{format_bytes, format_date, is_newer, BucketScan},
+};
+
+const HEADERS: [&str; 7] = [
+    "Bucket",
+    "Current",
+    "Noncurrent",
+    "Delete markers",
+    "Uploads",
+    "Size",
+    "Newest write",
+];
+
+/// What deleting a set of buckets would remove, one scan per bucket. Scans that stopped early give lower bounds, with
+/// CloudWatch's estimate for the whole bucket where there is one
+pub struct ImpactReport<'a> {
+    scans: &'a [(String, Result<BucketScan, aws_sdk_s3::Error>)],
+}
+
+impl<'a> ImpactReport<'a> {
+    pub fn new(scans: &'a [(String, Result<BucketScan, aws_sdk_s3::Error>)]) -> ImpactReport<'a> {
+        ImpactReport { scans }
+    }
+
+    fn rows(&self) -> Vec<[String; 7]> {
+        let mut rows = vec![HEADERS.map(str::to_owned)];
+        let mut total = BucketScan::default();
+
+        for (bucket, scan) in self.scans {
+            match scan {
+                Ok(scan) => {
+                    rows.push(row(bucket, scan));
+                    total.versions += scan.versions;
+                    total.current_versions += scan.current_versions;
+                    total.delete_markers += scan.delete_markers;
+                    total.multipart_uploads += scan.multipart_uploads;
+                    total.total_bytes += scan.total_bytes;
+                    total.sampled |= scan.sampled;
+                    if let Some(modified) = &scan.newest_last_modified {
+                        if total
+                            .newest_last_modified
+                            .is_none_or(|newest| is_newer(modified, &newest))
+                        {
+                            total.newest_last_modified = Some(*modified);
+                        }
+                    }
+                }
+                Err(_) => rows.push([
+                    bucket.to_owned(),
+                    "?".into(),
+                    "?".into(),
+                    "?".into(),
+                    "?".into(),
+                    "?".into(),
+                    "?".into(),
+                ]),
+            }
+        }
+
+        if self.scans.len() > 1 {
+            rows.push(row("Total", &total));
+        }
+        rows
+    }
+}
+
+impl fmt::Display for ImpactReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows = self.rows();
+        let mut widths = [0; 7];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in &rows {
+            let mut line = format!("{:<width$}", row[0], width = widths[0]);
+            for (cell, width) in row.iter().zip(widths).skip(1) {
+                line.push_str(&format!("  {:>width$}", cell, width = width));
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        let mut by_class: BTreeMap<&str, u64> = BTreeMap::new();
+        for (_, scan) in self.scans {
+            for (class, bytes) in scan.iter().flat_map(|scan| &scan.bytes_by_storage_class) {
+                *by_class.entry(class).or_default() += bytes;
+            }
+        }
+        if !by_class.is_empty() {
+            let classes: Vec<_> = by_class
+                .iter()
+                .map(|(class, bytes)| format!("{} {}", class, format_bytes(*bytes)))
+                .collect();
+            writeln!(f, "By storage class: {}", classes.join(", "))?;
+        }
+
+        let sampled = self
+            .scans
+            .iter()
+            .any(|(_, scan)| scan.as_ref().is_ok_and(|scan| scan.sampled));
+        if sampled {
+            writeln!(
+                f,
+                "Figures marked ≥ are lower bounds, counting only the listings read before scanning stopped"
+            )?;
+        }
+
+        for (bucket, scan) in self.scans {
+            match scan {
+                Ok(scan) if scan.sampled => {
+                    writeln!(
+                        f,
+                        "Stopped scanning {} after {} pages of object versions and {} of multipart uploads",
+                        bucket,
+                        scan.api_calls.list_object_versions,
+                        scan.api_calls.list_multipart_uploads
+                    )?;
+                    match &scan.estimate {
+                        Some(estimate) => writeln!(
+                            f,
+                            "  CloudWatch estimates it holds {} objects and {}, as of {}",
+                            estimate.objects.map_or_else(
+                                || "?".to_owned(),
+                                |objects| format!("about {}", objects)
+                            ),
+                            estimate.bytes.map_or_else(
+                                || "? bytes".to_owned(),
+                                |bytes| format!("about {}", format_bytes(bytes))
+                            ),
+                            format_day(&estimate.recorded)
+                        )?,
+                        None => writeln!(
+                            f,
+                            "  CloudWatch has no storage metrics for it to estimate from"
+                        )?,
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => writeln!(f, "Couldn't scan {}: {}", bucket, err)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn row(bucket: &str, scan: &BucketScan) -> [String; 7] {
+    let count = |count: usize| match scan.sampled {
+        true => format!("≥{}", count),
+        false => count.to_string(),
+    };
+
+    [
+        bucket.to_owned(),
+        count(scan.current_versions),
+        count(scan.noncurrent_versions()),
+        count(scan.delete_markers),
+        count(scan.multipart_uploads),
+        match scan.sampled {
+            true => format!("≥{}", format_bytes(scan.total_bytes)),
+            false => format_bytes(scan.total_bytes),
+        },
+        // Listings come in key order, so a sampled scan may have missed the newest write
+        match (scan.newest_last_modified, scan.sampled) {
+            (Some(modified), false) => format_date(&modified),
+            (Some(modified), true) => format!("{} or later", format_date(&modified)),
+            (None, false) => "-".into(),
+            (None, true) => "?".into(),
+        },
+    ]
+}
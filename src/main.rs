@@ -1,119 +1,921 @@
 #![allow(clippy::result_large_err)]
 
-use aws_config::meta::region::RegionProviderChain;
-use aws_sdk_s3::Client;
+use aws_config::{
+    meta::region::RegionProviderChain, profile::ProfileFileRegionProvider, BehaviorVersion,
+};
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::{Delete, ObjectIdentifier};
+use aws_sdk_s3::{config::Region, Client};
+use buckets::{Activity, BucketSummary, ListFilter};
+use clap::Parser;
+use cli::{Args, Command, Selection};
+use clients::Clients;
+use config::{Config, Confirmation, MAX_BUCKETS};
+use identity::Identity;
+use impact::ImpactReport;
 use inquire::{
-    list_option::ListOption, validator::Validation, Confirm, CustomUserError, MultiSelect,
+    list_option::ListOption,
+    validator::{ErrorMessage, Validation},
+    Confirm, CustomUserError, MultiSelect, Select, Text,
 };
-use std::process;
+use plan::Plan;
+use scan::BucketScan;
+use std::{env, fmt, num::NonZeroUsize, path::Path, process, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, Mutex};
+
+mod buckets;
+mod cli;
+mod clients;
+mod config;
+mod identity;
+mod impact;
+mod metrics;
+mod plan;
+mod profiles;
+mod protection;
+mod scan;
 
-const MAX_BUCKETS: u8 = 5;
-const PROTECTED_BUCKET_NAMES: &[&str] = &["backup", "do-not-delete", "console"];
+const DELETE_BATCH_SIZE: usize = 1000;
 
 #[tokio::main]
 async fn main() {
-    let region_provider = RegionProviderChain::default_provider().or_else("us-east-1");
-    let config = aws_config::from_env().region(region_provider).load().await;
-    let client = Client::new(&config);
+    let mut args = Args::parse();
+    let command = args.command.take().unwrap_or_default();
 
-    println!("Finding buckets...");
+    if command.deletes() && !args.yes && !cli::has_tty() {
+        eprintln!("No TTY detected. Pass --yes to run non-interactively");
+        process::exit(1);
+    }
 
-    let found_buckets = list_buckets(&client).await.unwrap_or_else(|err| {
+    let mut config = Config::load().unwrap_or_else(|err| {
         eprintln!("{}", err);
         process::exit(1)
     });
+    // The command line can lower the limits from config, but raising them needs --i-know-what-im-doing
+    config.max_buckets = match args.max_buckets {
+        Some(max) if max > config.max_buckets && !args.i_know_what_im_doing => {
+            eprintln!(
+                "Raising the bucket limit above {} needs --i-know-what-im-doing",
+                config.max_buckets
+            );
+            process::exit(1);
+        }
+        Some(max) => max,
+        None => config.max_buckets,
+    };
+    config.max_objects = match (args.max_objects, config.max_objects) {
+        (Some(max), Some(limit)) if max > limit && !args.i_know_what_im_doing => {
+            eprintln!(
+                "Raising the object limit above {} needs --i-know-what-im-doing",
+                limit
+            );
+            process::exit(1);
+        }
+        (max, limit) => max.or(limit),
+    };
+    config.i_know_what_im_doing = args.i_know_what_im_doing;
+    config.endpoint_url = args.endpoint_url.clone().or(config.endpoint_url);
+    config.force_path_style |= args.force_path_style;
 
-    let selected_buckets = MultiSelect::new("Select buckets to be removed", found_buckets)
-        .with_validator(wrapper_validator)
-        .prompt()
-        .unwrap();
+    let profile = args.profile.clone().or_else(|| pick_profile(&args));
+    let region_provider = match (&args.region, &profile) {
+        (Some(region), _) => RegionProviderChain::first_try(Region::new(region.to_owned())),
+        (None, Some(profile)) => RegionProviderChain::first_try(
+            ProfileFileRegionProvider::builder()
+                .profile_name(profile)
+                .build(),
+        )
+        .or_default_provider(),
+        (None, None) => RegionProviderChain::default_provider(),
+    }
+    .or_else("us-east-1");
+
+    let mut loader = aws_config::defaults(BehaviorVersion::latest()).region(region_provider);
+    if let Some(profile) = &profile {
+        loader = loader.profile_name(profile);
+    }
+    let sdk_config = loader.load().await;
+
+    // STS and IAM always go to AWS. Only S3 requests go to a custom endpoint
+    let identity = match identity::caller_identity(&sdk_config).await {
+        Ok(identity) => identity,
+        Err(err) if config.endpoint_url.is_some() && config.denied_accounts.is_empty() => {
+            eprintln!(
+                "Couldn't find caller identity, so running as an unknown account: {}",
+                err
+            );
+            Identity::unknown(&sdk_config)
+        }
+        Err(err) if config.endpoint_url.is_some() => {
+            eprintln!(
+                "Couldn't find caller identity, so denied_accounts can't be checked: {}",
+                err
+            );
+            process::exit(1)
+        }
+        Err(err) => {
+            eprintln!("Error finding caller identity: {}", err);
+            process::exit(1)
+        }
+    };
+    let clients = Clients::new(
+        sdk_config,
+        config.endpoint_url.clone(),
+        config.force_path_style,
+    );
+    println!("Using {}", identity);
+
+    config
+        .check_account(&identity.account_id)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1)
+        });
+
+    // Filters pick buckets themselves when there's no one to ask
+    let prompt = !args.yes && cli::has_tty();
+    let selected_buckets = match &command {
+        Command::Delete {
+            selection,
+            dry_run: only_report,
+        } => {
+            let selected_buckets =
+                select_buckets(&clients, &config, selection, prompt, args.scan_pages.get()).await;
+            if *only_report {
+                let scanned = dry_run(&clients, &selected_buckets).await;
+                process::exit(if scanned { 0 } else { 1 });
+            }
+            selected_buckets
+        }
+        Command::Plan { output, selection } => {
+            let selected_buckets =
+                select_buckets(&clients, &config, selection, prompt, args.scan_pages.get()).await;
+            write_plan(&identity, &clients, &selected_buckets, output).await;
+            return;
+        }
+        Command::Apply { plan } => check_plan(&identity, &config, &clients, plan).await,
+    };
+
+    println!("Scanning buckets...");
+    let mut scans = scan::scan_buckets(
+        &clients,
+        &selected_buckets,
+        args.scan_pages.get(),
+        config.max_objects,
+    )
+    .await;
+
+    if let Some(max_objects) = config.max_objects {
+        check_object_limit(max_objects, &scans);
+    }
+
+    metrics::estimate_sampled(&clients, &mut scans).await;
+
+    let mut typed_confirmation = config.confirmation.clone();
+    typed_confirmation.type_names |= args.type_names;
+    if args.yes {
+        refuse_typed_names(&typed_confirmation, &scans);
+    }
 
     println!("Deleting {} buckets", selected_buckets.len());
-    println!("{}", selected_buckets.join("\n\t - "));
+    print!("{}", ImpactReport::new(&scans));
 
-    let confirmation = Confirm::new(
-        format!(
-            "Do you wish to proceed? This action will delete {} buckets",
-            selected_buckets.len()
+    let confirmation = args.yes
+        || Confirm::new(
+            format!(
+                "Do you wish to proceed? This action will delete {} buckets in account {}",
+                selected_buckets.len(),
+                identity.account()
+            )
+            .as_str(),
         )
-        .as_str(),
-    )
-    .with_default(false)
-    .with_help_message("There's no turning back from here")
-    .prompt()
-    .unwrap_or(false);
+        .with_default(false)
+        .with_help_message(&format!(
+            "There's no turning back from here. Running as {} in {}",
+            identity.arn, identity.region
+        ))
+        .prompt()
+        .unwrap_or(false);
 
     if !confirmation {
         println!("Quitting");
         process::exit(1);
     }
 
+    if !args.yes {
+        confirm_names(&typed_confirmation, &scans);
+    }
+
+    let mut results = Vec::new();
     for bucket in selected_buckets {
         println!("Deleting bucket: {}", bucket);
-        empty_bucket(&client, &bucket).await.unwrap_or_else(|err| {
-            eprintln!("Error emptying bucket {}: {}", bucket, err);
+        let outcome = remove_bucket(&clients, &bucket, args.concurrency).await;
+        results.push((bucket, outcome));
+    }
+
+    print_summary(&results);
+
+    if results
+        .iter()
+        .any(|(_, outcome)| !matches!(outcome, BucketOutcome::Deleted))
+    {
+        process::exit(1);
+    }
+
+    println!("Done! 💥")
+}
+
+/// Asks which profile to use when there's a choice and nothing has made it already
+fn pick_profile(args: &Args) -> Option<String> {
+    if args.yes || env::var_os("AWS_PROFILE").is_some() || !cli::has_tty() {
+        return None;
+    }
+
+    let profiles = profiles::profile_names();
+    if profiles.len() < 2 {
+        return None;
+    }
+
+    let profile = Select::new("Select an AWS profile", profiles)
+        .prompt()
+        .unwrap_or_else(|_| {
+            println!("Quitting");
+            process::exit(1)
         });
-        delete_bucket(&client, &bucket).await.unwrap_or_else(|err| {
-            eprintln!("Error deleting bucket {}: {}", bucket, err);
+    Some(profile)
+}
+
+async fn find_buckets(
+    clients: &Clients,
+    config: &Config,
+    filter: &ListFilter,
+    keep: impl Fn(&str, Option<&DateTime>) -> bool,
+) -> Vec<BucketSummary> {
+    println!("Finding buckets...");
+
+    let mut buckets = buckets::list_buckets(clients, filter, keep)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1)
         });
+
+    for bucket in &mut buckets {
+        bucket.protection = protection(config, bucket);
     }
+    buckets
+}
 
-    println!("Done! 💥")
+async fn select_buckets(
+    clients: &Clients,
+    config: &Config,
+    selection: &Selection,
+    prompt: bool,
+    scan_pages: usize,
+) -> Vec<String> {
+    if selection.is_interactive() {
+        let auto_select = selection.has_filters() && !prompt;
+        if !auto_select && !cli::has_tty() {
+            eprintln!(
+                "No buckets given. Pass bucket names, --from-file or filters when running without a TTY"
+            );
+            process::exit(1);
+        }
+
+        let mut found_buckets = find_buckets(
+            clients,
+            config,
+            &selection.list_filter(),
+            |name, created| selection.matches_listing(name, created),
+        )
+        .await;
+        apply_filters(clients, selection, &mut found_buckets, scan_pages).await;
+
+        let (protected, selectable): (Vec<_>, Vec<_>) = found_buckets
+            .into_iter()
+            .partition(|bucket| bucket.protection.is_some());
+
+        if !protected.is_empty() {
+            println!("Protected buckets, which can't be selected:");
+            for bucket in &protected {
+                println!("\t - {}", bucket);
+            }
+        }
+
+        if selectable.is_empty() {
+            eprintln!("No buckets can be deleted");
+            process::exit(1);
+        }
+
+        if auto_select {
+            let names: Vec<_> = selectable
+                .iter()
+                .map(|bucket| bucket.name.clone())
+                .collect();
+            validate_requested(config, &names, &selectable).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1)
+            });
+            return names;
+        }
+
+        return MultiSelect::new("Select buckets to be removed", selectable)
+            .with_validator(selection_validator(config))
+            .prompt()
+            .unwrap_or_else(|_| {
+                println!("Quitting");
+                process::exit(1)
+            })
+            .into_iter()
+            .map(|bucket| bucket.name)
+            .collect();
+    }
+
+    let requested = selection.requested_buckets().unwrap_or_else(|err| {
+        eprintln!("Error reading bucket list: {}", err);
+        process::exit(1)
+    });
+
+    let mut found_buckets = find_buckets(
+        clients,
+        config,
+        &selection.list_filter(),
+        |name, created| {
+            requested.iter().any(|bucket| bucket == name)
+                && selection.matches_listing(name, created)
+        },
+    )
+    .await;
+    apply_filters(clients, selection, &mut found_buckets, scan_pages).await;
+
+    validate_requested(config, &requested, &found_buckets).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1)
+    });
+    requested
+}
+
+/// Applies the filters needing more than ListBuckets returns, protecting buckets written within `--inactive-for`
+async fn apply_filters(
+    clients: &Clients,
+    selection: &Selection,
+    buckets: &mut Vec<BucketSummary>,
+    scan_pages: usize,
+) {
+    buckets.retain(|bucket| selection.matches(bucket));
+
+    if let Some(age) = selection.inactive_for {
+        println!("Checking when buckets were last written...");
+        buckets::fetch_activity(clients, buckets, scan_pages).await;
+
+        for bucket in buckets.iter_mut() {
+            if bucket.protection.is_none() {
+                bucket.protection = activity_protection(age, bucket);
+            }
+        }
+    }
+}
+
+async fn write_plan(identity: &Identity, clients: &Clients, buckets: &[String], output: &Path) {
+    let plan = Plan::create(clients, &identity.account_id, buckets)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Error scanning buckets: {}", err);
+            process::exit(1)
+        });
+
+    plan.write(output).unwrap_or_else(|err| {
+        eprintln!("Error writing plan to {}: {}", output.display(), err);
+        process::exit(1)
+    });
+
+    println!(
+        "Wrote a plan to delete {} buckets to {}",
+        buckets.len(),
+        output.display()
+    );
+}
+
+/// Re-checks every bucket in a plan, returning the buckets to delete if none have changed
+async fn check_plan(
+    identity: &Identity,
+    config: &Config,
+    clients: &Clients,
+    path: &Path,
+) -> Vec<String> {
+    let plan = Plan::read(path).unwrap_or_else(|err| {
+        eprintln!("Error reading plan {}: {}", path.display(), err);
+        process::exit(1)
+    });
+    let buckets = plan.bucket_names();
+
+    let found_buckets = find_buckets(clients, config, &ListFilter::default(), |name, _| {
+        buckets.iter().any(|bucket| bucket == name)
+    })
+    .await;
+    validate_requested(config, &buckets, &found_buckets).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1)
+    });
+
+    println!("Checking buckets against plan from {}...", plan.created_at);
+    let changed = plan
+        .changed_buckets(clients, &identity.account_id)
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Error scanning buckets: {}", err);
+            process::exit(1)
+        });
+
+    if !changed.is_empty() {
+        for (bucket, differences) in changed {
+            eprintln!(
+                "Bucket {} changed since the plan was made: {}",
+                bucket,
+                differences.join(", ")
+            );
+        }
+        eprintln!("Refusing to apply an outdated plan");
+        process::exit(1);
+    }
+
+    buckets
+}
+
+/// Quits if the buckets hold more objects between them than one run may delete
+fn check_object_limit(
+    max_objects: usize,
+    scans: &[(String, Result<BucketScan, aws_sdk_s3::Error>)],
+) {
+    let mut total = 0;
+    for (bucket, scan) in scans {
+        match scan {
+            Ok(scan) => total += scan.objects(),
+            Err(err) => {
+                eprintln!("Error scanning bucket {}: {}", bucket, err);
+                process::exit(1)
+            }
+        }
+    }
+
+    if total > max_objects {
+        eprintln!(
+            "Maximum of {} objects across all buckets. The selection has more than that",
+            max_objects
+        );
+        process::exit(1);
+    }
+}
+
+/// Makes the operator type the name of every high-risk bucket, quitting on a mismatch
+fn confirm_names(
+    confirmation: &Confirmation,
+    scans: &[(String, Result<BucketScan, aws_sdk_s3::Error>)],
+) {
+    for (bucket, scan) in scans {
+        if !needs_typed_name(confirmation, bucket, scan) {
+            continue;
+        }
+
+        let typed = Text::new(&format!("Type {} to confirm its deletion", bucket))
+            .with_help_message("The name must match exactly")
+            .prompt()
+            .unwrap_or_default();
+
+        if typed != *bucket {
+            println!("{:?} doesn't match {}. Quitting", typed, bucket);
+            process::exit(1);
+        }
+    }
 }
 
-async fn empty_bucket(client: &Client, name: &String) -> Result<(), aws_sdk_s3::Error> {
-    let name = name.to_owned();
-    let objects = client.list_object_versions().bucket(&name).send().await?;
+/// Quits if any bucket needs its name typed, since `--yes` can't type it
+fn refuse_typed_names(
+    confirmation: &Confirmation,
+    scans: &[(String, Result<BucketScan, aws_sdk_s3::Error>)],
+) {
+    for (bucket, scan) in scans {
+        if needs_typed_name(confirmation, bucket, scan) {
+            eprintln!(
+                "{} needs its name typed to confirm deletion, which --yes can't do",
+                bucket
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Whether a bucket's name has to be typed. A sampled scan may have stopped short of a threshold, so it counts as
+/// above every threshold
+fn needs_typed_name(
+    confirmation: &Confirmation,
+    bucket: &str,
+    scan: &Result<BucketScan, aws_sdk_s3::Error>,
+) -> bool {
+    if confirmation.type_names {
+        return true;
+    }
+    if !confirmation.has_thresholds() {
+        return false;
+    }
+
+    match scan {
+        Ok(scan) => {
+            scan.sampled
+                || confirmation
+                    .above_objects
+                    .is_some_and(|limit| scan.objects() > limit)
+                || confirmation
+                    .above_bytes
+                    .is_some_and(|limit| scan.total_bytes > limit)
+        }
+        Err(err) => {
+            eprintln!("Error scanning bucket {}: {}", bucket, err);
+            true
+        }
+    }
+}
 
-    for object in objects.versions().unwrap_or_default() {
-        client
-            .delete_object()
-            .bucket(&name)
-            .key(object.key().unwrap_or_default())
-            .version_id(object.version_id().unwrap_or_default())
+async fn dry_run(clients: &Clients, buckets: &[String]) -> bool {
+    println!("Dry run, nothing will be deleted");
+
+    let mut scanned = true;
+    for bucket in buckets {
+        match scan::scan_bucket(clients, bucket).await {
+            Ok(scan) => println!("{}\n{}", bucket, scan),
+            Err(err) => {
+                eprintln!("Error scanning bucket {}: {}", bucket, err);
+                scanned = false;
+            }
+        }
+    }
+
+    scanned
+}
+
+#[derive(Debug)]
+enum BucketOutcome {
+    Deleted,
+    EmptyFailed(String),
+    DeleteFailed(String),
+}
+
+impl fmt::Display for BucketOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BucketOutcome::Deleted => write!(f, "deleted"),
+            BucketOutcome::EmptyFailed(reason) => {
+                write!(f, "not deleted, emptying failed: {}", reason)
+            }
+            BucketOutcome::DeleteFailed(reason) => write!(f, "not deleted: {}", reason),
+        }
+    }
+}
+
+async fn remove_bucket(
+    clients: &Clients,
+    bucket: &str,
+    concurrency: NonZeroUsize,
+) -> BucketOutcome {
+    let client = match clients.for_bucket(bucket).await {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("Error finding the region of bucket {}: {}", bucket, err);
+            return BucketOutcome::EmptyFailed(err.to_string());
+        }
+    };
+
+    match empty_bucket(&client, bucket, concurrency).await {
+        Ok(removed) => {
+            println!(
+                "Removed {} object versions and {} delete markers from {}",
+                removed.versions, removed.delete_markers, bucket
+            );
+            for failure in &removed.failures {
+                eprintln!("Error deleting object from {}: {}", bucket, failure);
+            }
+            if !removed.failures.is_empty() {
+                return BucketOutcome::EmptyFailed(format!(
+                    "{} objects could not be deleted",
+                    removed.failures.len()
+                ));
+            }
+        }
+        Err(err) => {
+            eprintln!("Error emptying bucket {}: {}", bucket, err);
+            return BucketOutcome::EmptyFailed(err.to_string());
+        }
+    }
+
+    match abort_multipart_uploads(&client, bucket).await {
+        Ok(aborted) => println!("Aborted {} multipart uploads in {}", aborted, bucket),
+        Err(err) => {
+            eprintln!("Error aborting multipart uploads in {}: {}", bucket, err);
+            return BucketOutcome::EmptyFailed(err.to_string());
+        }
+    }
+
+    match delete_bucket(&client, bucket).await {
+        Ok(()) => BucketOutcome::Deleted,
+        Err(err) => {
+            eprintln!("Error deleting bucket {}: {}", bucket, err);
+            BucketOutcome::DeleteFailed(err.to_string())
+        }
+    }
+}
+
+fn print_summary(results: &[(String, BucketOutcome)]) {
+    let width = results
+        .iter()
+        .map(|(bucket, _)| bucket.len())
+        .max()
+        .unwrap_or_default();
+
+    println!("\nSummary");
+    for (bucket, outcome) in results {
+        println!("\t{:width$}  {}", bucket, outcome, width = width);
+    }
+}
+
+#[derive(Debug, Default)]
+struct EmptyBucketOutput {
+    versions: usize,
+    delete_markers: usize,
+    failures: Vec<DeleteFailure>,
+}
+
+#[derive(Debug)]
+struct DeleteFailure {
+    key: String,
+    version_id: String,
+    code: String,
+    message: String,
+}
+
+impl fmt::Display for DeleteFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (version {}): {} {}",
+            self.key, self.version_id, self.code, self.message
+        )
+    }
+}
+
+async fn empty_bucket(
+    client: &Client,
+    name: &str,
+    concurrency: NonZeroUsize,
+) -> Result<EmptyBucketOutput, aws_sdk_s3::Error> {
+    let (sender, receiver) = mpsc::channel(concurrency.get() * 2);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let workers: Vec<_> = (0..concurrency.get())
+        .map(|_| {
+            tokio::spawn(delete_worker(
+                client.clone(),
+                name.to_owned(),
+                Arc::clone(&receiver),
+            ))
+        })
+        .collect();
+    drop(receiver);
+
+    let listed = list_versions(client, name, sender).await;
+
+    let mut removed = EmptyBucketOutput::default();
+    let mut worker_error = None;
+    for worker in workers {
+        match worker.await.expect("delete worker panicked") {
+            Ok(output) => {
+                removed.versions += output.versions;
+                removed.delete_markers += output.delete_markers;
+                removed.failures.extend(output.failures);
+            }
+            Err(err) => worker_error = worker_error.or(Some(err)),
+        }
+    }
+
+    listed?;
+    match worker_error {
+        Some(err) => Err(err),
+        None => Ok(removed),
+    }
+}
+
+async fn list_versions(
+    client: &Client,
+    name: &str,
+    sender: mpsc::Sender<Vec<ObjectIdentifier>>,
+) -> Result<(), aws_sdk_s3::Error> {
+    let mut key_marker: Option<String> = None;
+    let mut version_id_marker: Option<String> = None;
+
+    loop {
+        let objects = client
+            .list_object_versions()
+            .bucket(name)
+            .set_key_marker(key_marker.take())
+            .set_version_id_marker(version_id_marker.take())
             .send()
             .await?;
+
+        let versions = objects
+            .versions()
+            .iter()
+            .map(|object| (object.key(), object.version_id()));
+        let delete_markers = objects
+            .delete_markers()
+            .iter()
+            .map(|marker| (marker.key(), marker.version_id()));
+        let identifiers: Vec<_> = versions
+            .chain(delete_markers)
+            .map(|(key, version_id)| {
+                ObjectIdentifier::builder()
+                    .set_key(key.map(str::to_owned))
+                    .set_version_id(version_id.map(str::to_owned))
+                    .build()
+            })
+            .collect::<Result<_, _>>()?;
+
+        for batch in identifiers.chunks(DELETE_BATCH_SIZE) {
+            // Every worker has stopped, so there is nobody left to delete what we list
+            if sender.send(batch.to_vec()).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        if !objects.is_truncated().unwrap_or_default() {
+            break;
+        }
+
+        key_marker = objects.next_key_marker().map(str::to_owned);
+        version_id_marker = objects.next_version_id_marker().map(str::to_owned);
     }
     Ok(())
 }
 
-async fn delete_bucket(client: &Client, name: &String) -> Result<(), aws_sdk_s3::Error> {
-    client.delete_bucket().bucket(name).send().await?;
+async fn delete_worker(
+    client: Client,
+    name: String,
+    receiver: Arc<Mutex<mpsc::Receiver<Vec<ObjectIdentifier>>>>,
+) -> Result<EmptyBucketOutput, aws_sdk_s3::Error> {
+    let mut removed = EmptyBucketOutput::default();
+
+    loop {
+        let batch = receiver.lock().await.recv().await;
+        let Some(batch) = batch else {
+            break;
+        };
+
+        if let Err(err) = delete_batch(&client, &name, batch, &mut removed).await {
+            // Stop the listing as well, rather than queueing work nobody will finish
+            receiver.lock().await.close();
+            return Err(err);
+        }
+    }
+    Ok(removed)
+}
 
+async fn delete_batch(
+    client: &Client,
+    name: &str,
+    batch: Vec<ObjectIdentifier>,
+    removed: &mut EmptyBucketOutput,
+) -> Result<(), aws_sdk_s3::Error> {
+    let response = client
+        .delete_objects()
+        .bucket(name)
+        .delete(Delete::builder().set_objects(Some(batch)).build()?)
+        .send()
+        .await?;
+
+    for deleted in response.deleted() {
+        if deleted.delete_marker().unwrap_or_default() {
+            removed.delete_markers += 1;
+        } else {
+            removed.versions += 1;
+        }
+    }
+
+    for error in response.errors() {
+        removed.failures.push(DeleteFailure {
+            key: error.key().unwrap_or_default().to_owned(),
+            version_id: error.version_id().unwrap_or_default().to_owned(),
+            code: error.code().unwrap_or_default().to_owned(),
+            message: error.message().unwrap_or_default().to_owned(),
+        });
+    }
     Ok(())
 }
 
-async fn list_buckets(client: &Client) -> Result<Vec<String>, aws_sdk_s3::Error> {
-    let response = client.list_buckets().send().await?;
+async fn abort_multipart_uploads(client: &Client, name: &str) -> Result<usize, aws_sdk_s3::Error> {
+    let mut aborted = 0;
+    let mut key_marker: Option<String> = None;
+    let mut upload_id_marker: Option<String> = None;
 
-    let buckets = response.buckets().unwrap_or_default().to_vec();
-    let ret: Vec<_> = buckets
-        .iter()
-        .map(|x| x.name().unwrap().to_owned())
-        .collect();
-    Ok(ret)
+    loop {
+        let uploads = client
+            .list_multipart_uploads()
+            .bucket(name)
+            .set_key_marker(key_marker.take())
+            .set_upload_id_marker(upload_id_marker.take())
+            .send()
+            .await?;
+
+        for upload in uploads.uploads() {
+            client
+                .abort_multipart_upload()
+                .bucket(name)
+                .key(upload.key().unwrap_or_default())
+                .upload_id(upload.upload_id().unwrap_or_default())
+                .send()
+                .await?;
+            aborted += 1;
+        }
+
+        if !uploads.is_truncated().unwrap_or_default() {
+            break;
+        }
+
+        key_marker = uploads.next_key_marker().map(str::to_owned);
+        upload_id_marker = uploads.next_upload_id_marker().map(str::to_owned);
+    }
+    Ok(aborted)
 }
 
-fn protect_names_validator(options: &[ListOption<&String>]) -> Result<Validation, CustomUserError> {
-    let invalid = options.iter().any(|option| {
-        PROTECTED_BUCKET_NAMES
-            .iter()
-            .any(|protected| option.value.contains(protected))
-    });
+async fn delete_bucket(client: &Client, name: &str) -> Result<(), aws_sdk_s3::Error> {
+    client.delete_bucket().bucket(name).send().await?;
 
-    match invalid {
-        false => Ok(Validation::Valid),
-        true => Ok(Validation::Invalid(
-            "Cannot delete buckets with protected names".into(),
+    Ok(())
+}
+
+/// Why a bucket's name protects it from deletion, if it does
+fn name_protection(config: &Config, bucket: &BucketSummary) -> Option<String> {
+    config
+        .protecting_rule(&bucket.name)
+        .map(|rule| format!("protected by rule {}", rule))
+}
+
+/// Why a bucket's tags protect it from deletion, if they do
+fn tag_protection(config: &Config, bucket: &BucketSummary) -> Option<String> {
+    if config.tag_rules.is_empty() {
+        return None;
+    }
+
+    match &bucket.tags {
+        None => Some("its tags couldn't be checked".into()),
+        Some(tags) => config.protecting_tag(tags).map(|(rule, (key, value))| {
+            format!("protected by rule {} (tagged {}={})", rule, key, value)
+        }),
+    }
+}
+
+/// Why a bucket's recent writes protect it from deletion, if they do. A bucket whose newest write couldn't be found
+/// is protected too
+fn activity_protection(age: Duration, bucket: &BucketSummary) -> Option<String> {
+    match bucket.activity {
+        None => Some("its activity couldn't be checked".into()),
+        Some(Activity {
+            last_write: Some(last_write),
+            ..
+        }) if !cli::is_older_than(last_write.secs(), age) => {
+            Some(format!("written to within {}", cli::format_age(age)))
+        }
+        Some(Activity { sampled: true, .. }) => Some(format!(
+            "too large to rule out writes within {}",
+            cli::format_age(age)
         )),
+        Some(_) => None,
     }
 }
 
-fn length_validator(options: &[ListOption<&String>]) -> Result<Validation, CustomUserError> {
+fn protection(config: &Config, bucket: &BucketSummary) -> Option<String> {
+    name_protection(config, bucket).or_else(|| tag_protection(config, bucket))
+}
+
+fn length_validator(
+    config: &Config,
+    options: &[ListOption<&BucketSummary>],
+) -> Result<Validation, CustomUserError> {
     let length = options.len();
-    if length > MAX_BUCKETS.into() {
+    if length > config.max_buckets {
         return Ok(Validation::Invalid(
-            format!("Maximum of {} selections. You have {}", MAX_BUCKETS, length).into(),
+            format!(
+                "Maximum of {} selections. You have {}",
+                config.max_buckets, length
+            )
+            .into(),
+        ));
+    }
+
+    if length > MAX_BUCKETS && !config.i_know_what_im_doing {
+        return Ok(Validation::Invalid(
+            format!(
+                "Selecting more than {} buckets needs --i-know-what-im-doing",
+                MAX_BUCKETS
+            )
+            .into(),
         ));
     }
 
@@ -124,13 +926,261 @@ fn length_validator(options: &[ListOption<&String>]) -> Result<Validation, Custo
     Ok(Validation::Valid)
 }
 
-fn wrapper_validator(options: &[ListOption<&String>]) -> Result<Validation, CustomUserError> {
-    let validators = [protect_names_validator, length_validator];
+/// Refuses buckets protected by name, tags or recent writes, as found when they were listed
+fn protection_validator(
+    options: &[ListOption<&BucketSummary>],
+) -> Result<Validation, CustomUserError> {
+    let protected = options.iter().find_map(|option| {
+        option
+            .value
+            .protection
+            .as_ref()
+            .map(|reason| (option.value, reason))
+    });
 
-    for validator in validators {
-        if let Ok(Validation::Invalid(error)) = validator(options) {
+    match protected {
+        None => Ok(Validation::Valid),
+        Some((bucket, reason)) => Ok(Validation::Invalid(
+            format!("Cannot delete {}, {}", bucket.name, reason).into(),
+        )),
+    }
+}
+
+/// Applies the selector's checks to buckets named on the command line
+fn validate_requested(
+    config: &Config,
+    requested: &[String],
+    found: &[BucketSummary],
+) -> Result<(), String> {
+    let mut options = Vec::with_capacity(requested.len());
+    for (index, name) in requested.iter().enumerate() {
+        match found.iter().find(|bucket| bucket.name == *name) {
+            Some(bucket) => options.push(ListOption::new(index, bucket)),
+            None => return Err(format!("Bucket {} not found", name)),
+        }
+    }
+
+    match wrapper_validator(config, &options) {
+        Ok(Validation::Valid) => Ok(()),
+        Ok(Validation::Invalid(ErrorMessage::Custom(message))) => Err(message),
+        Ok(Validation::Invalid(ErrorMessage::Default)) => Err("Invalid selection".into()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn wrapper_validator(
+    config: &Config,
+    options: &[ListOption<&BucketSummary>],
+) -> Result<Validation, CustomUserError> {
+    for validation in [
+        protection_validator(options),
+        length_validator(config, options),
+    ] {
+        if let Ok(Validation::Invalid(error)) = validation {
             return Ok(Validation::Invalid(error));
         }
     }
     Ok(Validation::Valid)
 }
+
+fn selection_validator(
+    config: &Config,
+) -> impl Fn(&[ListOption<&BucketSummary>]) -> Result<Validation, CustomUserError> + Clone {
+    let config = config.clone();
+    move |options| wrapper_validator(&config, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(objects: usize, bytes: u64, sampled: bool) -> Result<BucketScan, aws_sdk_s3::Error> {
+        Ok(BucketScan {
+            versions: objects,
+            current_versions: objects,
+            total_bytes: bytes,
+            sampled,
+            ..BucketScan::default()
+        })
+    }
+
+    fn failed_scan() -> Result<BucketScan, aws_sdk_s3::Error> {
+        Err(aws_sdk_s3::Error::NoSuchBucket(
+            aws_sdk_s3::types::error::NoSuchBucket::builder().build(),
+        ))
+    }
+
+    fn thresholds(objects: Option<usize>, bytes: Option<u64>) -> Confirmation {
+        Confirmation {
+            type_names: false,
+            above_objects: objects,
+            above_bytes: bytes,
+        }
+    }
+
+    #[test]
+    fn type_names_covers_every_bucket() {
+        let confirmation = Confirmation {
+            type_names: true,
+            ..Confirmation::default()
+        };
+        assert!(needs_typed_name(&confirmation, "logs", &scan(0, 0, false)));
+        assert!(needs_typed_name(&confirmation, "logs", &failed_scan()));
+    }
+
+    #[test]
+    fn no_thresholds_need_no_typing() {
+        let confirmation = Confirmation::default();
+        assert!(!needs_typed_name(
+            &confirmation,
+            "logs",
+            &scan(1_000_000, u64::MAX, true)
+        ));
+        assert!(!needs_typed_name(&confirmation, "logs", &failed_scan()));
+    }
+
+    #[test]
+    fn buckets_above_a_threshold_need_typing() {
+        let objects = thresholds(Some(100), None);
+        assert!(!needs_typed_name(
+            &objects,
+            "logs",
+            &scan(100, u64::MAX, false)
+        ));
+        assert!(needs_typed_name(&objects, "logs", &scan(101, 0, false)));
+
+        let bytes = thresholds(None, Some(1024));
+        assert!(!needs_typed_name(
+            &bytes,
+            "logs",
+            &scan(usize::MAX, 1024, false)
+        ));
+        assert!(needs_typed_name(&bytes, "logs", &scan(0, 1025, false)));
+    }
+
+    #[test]
+    fn sampled_scans_count_as_above_every_threshold() {
+        let confirmation = thresholds(Some(100), Some(1024));
+        assert!(needs_typed_name(&confirmation, "logs", &scan(0, 0, true)));
+    }
+
+    #[test]
+    fn failed_scans_need_typing() {
+        let confirmation = thresholds(Some(100), None);
+        assert!(needs_typed_name(&confirmation, "logs", &failed_scan()));
+    }
+
+    fn limits(max_buckets: usize, i_know_what_im_doing: bool) -> Config {
+        Config {
+            protection_rules: Vec::new(),
+            tag_rules: Vec::new(),
+            confirmation: Confirmation::default(),
+            max_buckets,
+            max_objects: None,
+            allowed_accounts: None,
+            denied_accounts: Vec::new(),
+            endpoint_url: None,
+            force_path_style: false,
+            i_know_what_im_doing,
+        }
+    }
+
+    fn select(config: &Config, count: usize) -> Result<(), String> {
+        let names: Vec<_> = (0..count)
+            .map(|index| format!("bucket-{}", index))
+            .collect();
+        let buckets: Vec<_> = names.iter().map(|name| bucket(name, None)).collect();
+        validate_requested(config, &names, &buckets)
+    }
+
+    #[test]
+    fn raised_bucket_limit_needs_flag_only_for_large_selections() {
+        let config = limits(40, false);
+        assert!(select(&config, 1).is_ok());
+        assert!(select(&config, MAX_BUCKETS).is_ok());
+        assert!(select(&config, MAX_BUCKETS + 1).is_err());
+
+        let config = limits(40, true);
+        assert!(select(&config, MAX_BUCKETS + 1).is_ok());
+        assert!(select(&config, 41).is_err());
+    }
+
+    #[test]
+    fn lowered_bucket_limit_applies() {
+        let config = limits(2, true);
+        assert!(select(&config, 2).is_ok());
+        assert!(select(&config, 3).is_err());
+        assert!(select(&config, 0).is_err());
+    }
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn days_ago(days: i64) -> DateTime {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        DateTime::from_secs(now - days * DAY)
+    }
+
+    fn bucket(name: &str, activity: Option<Activity>) -> BucketSummary {
+        BucketSummary {
+            name: name.into(),
+            created: None,
+            region: None,
+            versioning: None,
+            tags: None,
+            activity,
+            protection: None,
+        }
+    }
+
+    fn activity(last_write: Option<DateTime>, sampled: bool) -> Option<Activity> {
+        Some(Activity {
+            last_write,
+            sampled,
+        })
+    }
+
+    #[test]
+    fn unknown_activity_protects() {
+        let reason =
+            activity_protection(Duration::from_secs(30 * DAY as u64), &bucket("logs", None));
+        assert_eq!(reason.as_deref(), Some("its activity couldn't be checked"));
+    }
+
+    #[test]
+    fn recent_writes_protect() {
+        let age = Duration::from_secs(30 * DAY as u64);
+        for sampled in [false, true] {
+            let reason =
+                activity_protection(age, &bucket("logs", activity(Some(days_ago(2)), sampled)));
+            assert_eq!(reason.as_deref(), Some("written to within 30d"));
+        }
+    }
+
+    #[test]
+    fn sampled_buckets_without_recent_writes_protect() {
+        let age = Duration::from_secs(30 * DAY as u64);
+        for last_write in [Some(days_ago(90)), None] {
+            let reason = activity_protection(age, &bucket("logs", activity(last_write, true)));
+            assert_eq!(
+                reason.as_deref(),
+                Some("too large to rule out writes within 30d")
+            );
+        }
+    }
+
+    #[test]
+    fn old_and_empty_buckets_arent_protected() {
+        let age = Duration::from_secs(30 * DAY as u64);
+        assert_eq!(
+            activity_protection(age, &bucket("logs", activity(Some(days_ago(90)), false))),
+            None
+        );
+        assert_eq!(
+            activity_protection(age, &bucket("logs", activity(None, false))),
+            None
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use aws_sdk_cloudwatch::{
+    primitives::DateTime,
+    types::{Dimension, DimensionFilter, Statistic},
+    Client,
+};
+use std::time::SystemTime;
+
+use crate::{clients::Clients, scan::BucketScan};
+
+const S3_NAMESPACE: &str = "AWS/S3";
+const DAY_SECS: i64 = 24 * 60 * 60;
+/// S3 records storage metrics once a day, a day or two late
+const LOOKBACK_DAYS: i64 = 3;
+
+/// What CloudWatch's daily S3 storage metrics say a whole bucket holds
+#[derive(Debug, Clone, Copy)]
+pub struct StorageMetrics {
+    /// Object versions, delete markers and multipart upload parts
+    pub objects: Option<u64>,
+    /// Bytes across every storage class
+    pub bytes: Option<u64>,
+    /// When the newest figure was recorded
+    pub recorded: DateTime,
+}
+
+/// Estimates the size of every bucket whose scan stopped early from its storage metrics. Buckets without metrics,
+/// such as new ones or those on an S3-compatible store, are left without an estimate
+pub async fn estimate_sampled(
+    clients: &Clients,
+    scans: &mut [(String, Result<BucketScan, aws_sdk_s3::Error>)],
+) {
+    for (bucket, scan) in scans {
+        if let Ok(scan) = scan {
+            if scan.sampled {
+                scan.estimate = storage_metrics(clients, bucket).await;
+            }
+        }
+    }
+}
+
+/// Not every principal may read metrics, so this is best effort
+async fn storage_metrics(clients: &Clients, bucket: &str) -> Option<StorageMetrics> {
+    let client = clients.cloudwatch_for_bucket(bucket).await?;
+    let objects = latest_average(&client, bucket, "NumberOfObjects", "AllStorageTypes").await;
+
+    // Sizes are recorded for each storage type separately, so find which ones the bucket has
+    let listed = client
+        .list_metrics()
+        .namespace(S3_NAMESPACE)
+        .metric_name("BucketSizeBytes")
+        .dimensions(
+            DimensionFilter::builder()
+                .name("BucketName")
+                .value(bucket)
+                .build(),
+        )
+        .send()
+        .await
+        .ok();
+    let storage_types = listed
+        .iter()
+        .flat_map(|listed| listed.metrics())
+        .filter_map(|metric| {
+            metric
+                .dimensions()
+                .iter()
+                .find(|dimension| dimension.name() == Some("StorageType"))
+                .and_then(Dimension::value)
+        });
+
+    let mut sizes = Vec::new();
+    for storage_type in storage_types {
+        sizes.extend(latest_average(&client, bucket, "BucketSizeBytes", storage_type).await);
+    }
+
+    let recorded = objects
+        .iter()
+        .chain(&sizes)
+        .map(|(_, recorded)| *recorded)
+        .max_by_key(DateTime::secs)?;
+    Some(StorageMetrics {
+        objects: objects.map(|(objects, _)| objects),
+        bytes: match sizes.is_empty() {
+            true => None,
+            false => Some(sizes.iter().map(|(bytes, _)| bytes).sum()),
+        },
+        recorded,
+    })
+}
+
+/// The newest daily figure for one of a bucket's storage metrics, and when it was recorded
+async fn latest_average(
+    client: &Client,
+    bucket: &str,
+    metric: &str,
+    storage_type: &str,
+) -> Option<(u64, DateTime)> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    let response = client
+        .get_metric_statistics()
+        .namespace(S3_NAMESPACE)
+        .metric_name(metric)
+        .dimensions(
+            Dimension::builder()
+                .name("BucketName")
+                .value(bucket)
+                .build(),
+        )
+        .dimensions(
+            Dimension::builder()
+                .name("StorageType")
+                .value(storage_type)
+                .build(),
+        )
+        .start_time(DateTime::from_secs(now - LOOKBACK_DAYS * DAY_SECS))
+        .end_time(DateTime::from_secs(now))
+        .period(DAY_SECS as i32)
+        .statistics(Statistic::Average)
+        .send()
+        .await
+        .ok()?;
+
+    response
+        .datapoints()
+        .iter()
+        .filter_map(|point| Some((point.average()?.max(0.0) as u64, *point.timestamp()?)))
+        .max_by_key(|(_, recorded)| recorded.secs())
+}
@@ -0,0 +1,217 @@
+use aws_sdk_s3::primitives::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path, time::SystemTime};
+
+use crate::{
+    clients::Clients,
+    scan::{self, format_date},
+};
+
+/// Buckets chosen for deletion, written by `s3-bang plan` and checked by `s3-bang apply`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    pub created_at: String,
+    pub buckets: Vec<PlannedBucket>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlannedBucket {
+    pub name: String,
+    pub fingerprint: Fingerprint,
+}
+
+/// Enough about a bucket to notice it has been written to, or belongs to another account
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub account_id: String,
+    pub versions: usize,
+    pub delete_markers: usize,
+    pub newest_last_modified: Option<String>,
+}
+
+impl Fingerprint {
+    pub async fn take(
+        clients: &Clients,
+        account_id: &str,
+        bucket: &str,
+    ) -> Result<Fingerprint, aws_sdk_s3::Error> {
+        let scan = scan::scan_bucket(clients, bucket).await?;
+
+        Ok(Fingerprint {
+            account_id: account_id.to_owned(),
+            versions: scan.versions,
+            delete_markers: scan.delete_markers,
+            newest_last_modified: scan
+                .newest_last_modified
+                .map(|modified| format_date(&modified)),
+        })
+    }
+
+    /// Human readable list of what differs between two fingerprints
+    pub fn differences(&self, current: &Fingerprint) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.account_id != current.account_id {
+            differences.push(format!(
+                "account {} -> {}",
+                self.account_id, current.account_id
+            ));
+        }
+        if self.versions != current.versions {
+            differences.push(format!(
+                "object versions {} -> {}",
+                self.versions, current.versions
+            ));
+        }
+        if self.delete_markers != current.delete_markers {
+            differences.push(format!(
+                "delete markers {} -> {}",
+                self.delete_markers, current.delete_markers
+            ));
+        }
+        if self.newest_last_modified != current.newest_last_modified {
+            differences.push(format!(
+                "last modified {} -> {}",
+                self.newest_last_modified.as_deref().unwrap_or("never"),
+                current.newest_last_modified.as_deref().unwrap_or("never")
+            ));
+        }
+
+        differences
+    }
+}
+
+impl Plan {
+    pub async fn create(
+        clients: &Clients,
+        account_id: &str,
+        buckets: &[String],
+    ) -> Result<Plan, aws_sdk_s3::Error> {
+        let mut planned = Vec::new();
+        for bucket in buckets {
+            planned.push(PlannedBucket {
+                name: bucket.to_owned(),
+                fingerprint: Fingerprint::take(clients, account_id, bucket).await?,
+            });
+        }
+
+        Ok(Plan {
+            created_at: format_date(&DateTime::from(SystemTime::now())),
+            buckets: planned,
+        })
+    }
+
+    pub fn read(path: &Path) -> io::Result<Plan> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents + "\n")
+    }
+
+    pub fn bucket_names(&self) -> Vec<String> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.name.to_owned())
+            .collect()
+    }
+
+    /// Buckets whose fingerprint no longer matches, with what changed
+    pub async fn changed_buckets(
+        &self,
+        clients: &Clients,
+        account_id: &str,
+    ) -> Result<Vec<(String, Vec<String>)>, aws_sdk_s3::Error> {
+        let mut changed = Vec::new();
+        for bucket in &self.buckets {
+            let current = Fingerprint::take(clients, account_id, &bucket.name).await?;
+            if bucket.fingerprint != current {
+                changed.push((
+                    bucket.name.to_owned(),
+                    bucket.fingerprint.differences(&current),
+                ));
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint() -> Fingerprint {
+        Fingerprint {
+            account_id: "111111111111".into(),
+            versions: 10,
+            delete_markers: 2,
+            newest_last_modified: Some("2026-01-01T00:00:00Z".into()),
+        }
+    }
+
+    #[test]
+    fn unchanged_fingerprints_match() {
+        assert_eq!(fingerprint(), fingerprint());
+        assert!(fingerprint().differences(&fingerprint()).is_empty());
+    }
+
+    #[test]
+    fn each_change_is_noticed() {
+        let changes = [
+            (
+                Fingerprint {
+                    account_id: "222222222222".into(),
+                    ..fingerprint()
+                },
+                "account 111111111111 -> 222222222222",
+            ),
+            (
+                Fingerprint {
+                    versions: 11,
+                    ..fingerprint()
+                },
+                "object versions 10 -> 11",
+            ),
+            (
+                Fingerprint {
+                    delete_markers: 0,
+                    ..fingerprint()
+                },
+                "delete markers 2 -> 0",
+            ),
+            (
+                Fingerprint {
+                    newest_last_modified: Some("2026-02-01T00:00:00Z".into()),
+                    ..fingerprint()
+                },
+                "last modified 2026-01-01T00:00:00Z -> 2026-02-01T00:00:00Z",
+            ),
+            (
+                Fingerprint {
+                    newest_last_modified: None,
+                    ..fingerprint()
+                },
+                "last modified 2026-01-01T00:00:00Z -> never",
+            ),
+        ];
+
+        for (current, difference) in changes {
+            assert_ne!(fingerprint(), current);
+            assert_eq!(fingerprint().differences(&current), [difference]);
+        }
+    }
+
+    #[test]
+    fn every_change_is_listed() {
+        let current = Fingerprint {
+            account_id: "222222222222".into(),
+            versions: 0,
+            delete_markers: 0,
+            newest_last_modified: None,
+        };
+        assert_eq!(fingerprint().differences(&current).len(), 4);
+    }
+}
@@ -0,0 +1,80 @@
+use std::{env, fs, path::PathBuf};
+
+/// Profiles defined in the shared AWS config file
+pub fn profile_names() -> Vec<String> {
+    let path = env::var_os("AWS_CONFIG_FILE")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".aws").join("config")));
+    let Some(contents) = path.and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    parse_profile_names(&contents)
+}
+
+/// Profile names in the order the config file defines them, once each
+fn parse_profile_names(contents: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let Some(section) = line
+            .trim()
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        else {
+            continue;
+        };
+
+        // Other sections, such as `[sso-session name]`, aren't profiles
+        let name = match section.trim().split_once(char::is_whitespace) {
+            None if section.trim() == "default" => "default",
+            Some(("profile", name)) => name.trim(),
+            _ => continue,
+        };
+
+        if !names.iter().any(|existing| existing == name) {
+            names.push(name.to_owned());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_default_and_named_profiles() {
+        let contents = "\
+[default]
+region = eu-west-1
+
+[profile dev]
+region = us-east-1
+
+[ profile  staging ]
+sso_session = work
+
+[profile dev]
+output = json
+";
+        assert_eq!(parse_profile_names(contents), ["default", "dev", "staging"]);
+    }
+
+    #[test]
+    fn skips_sections_that_arent_profiles() {
+        let contents = "\
+[sso-session work]
+sso_start_url = https://example.awsapps.com/start
+
+[services local]
+s3 =
+  endpoint_url = http://localhost:9000
+
+[profile prod]
+sso_session = work
+
+[profile]
+[dev]
+";
+        assert_eq!(parse_profile_names(contents), ["prod"]);
+    }
+}
@@ -0,0 +1,348 @@
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Contains,
+    Exact,
+    Prefix,
+    Suffix,
+    Glob,
+    Regex,
+}
+
+/// A protection rule as written in a config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub pattern: String,
+    #[serde(rename = "match", default)]
+    pub mode: MatchMode,
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+/// Bucket names matching a rule can't be deleted
+#[derive(Debug, Clone)]
+pub struct ProtectionRule {
+    config: RuleConfig,
+    matcher: Matcher,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Text(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl ProtectionRule {
+    pub fn new(config: RuleConfig) -> Result<ProtectionRule, String> {
+        let matcher = match config.mode {
+            MatchMode::Glob => GlobBuilder::new(&config.pattern)
+                .case_insensitive(config.case_insensitive)
+                .build()
+                .map(|glob| Matcher::Glob(glob.compile_matcher()))
+                .map_err(|err| err.to_string())?,
+            MatchMode::Regex => RegexBuilder::new(&config.pattern)
+                .case_insensitive(config.case_insensitive)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|err| err.to_string())?,
+            _ if config.case_insensitive => Matcher::Text(config.pattern.to_lowercase()),
+            _ => Matcher::Text(config.pattern.clone()),
+        };
+
+        Ok(ProtectionRule { config, matcher })
+    }
+
+    /// Rule matching any name containing `pattern`
+    pub fn contains(pattern: &str) -> ProtectionRule {
+        ProtectionRule {
+            config: RuleConfig {
+                pattern: pattern.to_owned(),
+                mode: MatchMode::Contains,
+                case_insensitive: false,
+            },
+            matcher: Matcher::Text(pattern.to_owned()),
+        }
+    }
+
+    /// Text every matching name starts with, for rules that have one
+    pub fn literal_prefix(&self) -> Option<&str> {
+        if self.config.case_insensitive {
+            return None;
+        }
+
+        let pattern = self.config.pattern.as_str();
+        let prefix = match self.config.mode {
+            MatchMode::Exact | MatchMode::Prefix => pattern,
+            MatchMode::Glob => {
+                let end = pattern
+                    .find(['*', '?', '[', '{', '\\'])
+                    .unwrap_or(pattern.len());
+                &pattern[..end]
+            }
+            _ => return None,
+        };
+
+        match prefix.is_empty() {
+            true => None,
+            false => Some(prefix),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let text = match &self.matcher {
+            Matcher::Glob(glob) => return glob.is_match(name),
+            Matcher::Regex(regex) => return regex.is_match(name),
+            Matcher::Text(text) => text,
+        };
+
+        let name = match self.config.case_insensitive {
+            true => name.to_lowercase(),
+            false => name.to_owned(),
+        };
+
+        match self.config.mode {
+            MatchMode::Exact => name == *text,
+            MatchMode::Prefix => name.starts_with(text.as_str()),
+            MatchMode::Suffix => name.ends_with(text.as_str()),
+            _ => name.contains(text.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for ProtectionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mode = match self.config.mode {
+            MatchMode::Contains => "contains",
+            MatchMode::Exact => "exact",
+            MatchMode::Prefix => "prefix",
+            MatchMode::Suffix => "suffix",
+            MatchMode::Glob => "glob",
+            MatchMode::Regex => "regex",
+        };
+        write!(f, "{} {:?}", mode, self.config.pattern)?;
+
+        if self.config.case_insensitive {
+            write!(f, " (case-insensitive)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Buckets carrying a matching tag can't be deleted. Without a value, any value matches
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TagRule {
+    pub key: String,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub case_insensitive: bool,
+}
+
+impl TagRule {
+    pub fn matches(&self, key: &str, value: &str) -> bool {
+        let equals = |a: &str, b: &str| match self.case_insensitive {
+            true => a.eq_ignore_ascii_case(b),
+            false => a == b,
+        };
+
+        equals(&self.key, key)
+            && self
+                .value
+                .as_deref()
+                .is_none_or(|expected| equals(expected, value))
+    }
+}
+
+impl fmt::Display for TagRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "tag {}={}", self.key, value)?,
+            None => write!(f, "tag {}", self.key)?,
+        }
+
+        if self.case_insensitive {
+            write!(f, " (case-insensitive)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, mode: MatchMode, case_insensitive: bool) -> ProtectionRule {
+        ProtectionRule::new(RuleConfig {
+            pattern: pattern.to_owned(),
+            mode,
+            case_insensitive,
+        })
+        .unwrap()
+    }
+
+    fn tag(key: &str, value: Option<&str>, case_insensitive: bool) -> TagRule {
+        TagRule {
+            key: key.to_owned(),
+            value: value.map(str::to_owned),
+            case_insensitive,
+        }
+    }
+
+    #[test]
+    fn contains_matches_anywhere() {
+        let sensitive = rule("prod", MatchMode::Contains, false);
+        assert!(sensitive.matches("app-prod-logs"));
+        assert!(!sensitive.matches("app-PROD-logs"));
+        assert!(!sensitive.matches("app-staging"));
+
+        let insensitive = rule("Prod", MatchMode::Contains, true);
+        assert!(insensitive.matches("app-prod-logs"));
+        assert!(insensitive.matches("app-PROD-logs"));
+    }
+
+    #[test]
+    fn exact_matches_whole_name() {
+        let sensitive = rule("data", MatchMode::Exact, false);
+        assert!(sensitive.matches("data"));
+        assert!(!sensitive.matches("Data"));
+        assert!(!sensitive.matches("data-1"));
+
+        let insensitive = rule("Data", MatchMode::Exact, true);
+        assert!(insensitive.matches("data"));
+        assert!(!insensitive.matches("data-1"));
+    }
+
+    #[test]
+    fn prefix_and_suffix_match_ends() {
+        let prefix = rule("shared-", MatchMode::Prefix, false);
+        assert!(prefix.matches("shared-assets"));
+        assert!(!prefix.matches("Shared-assets"));
+        assert!(!prefix.matches("not-shared-assets"));
+        assert!(rule("Shared-", MatchMode::Prefix, true).matches("shared-assets"));
+
+        let suffix = rule("-keep", MatchMode::Suffix, false);
+        assert!(suffix.matches("logs-keep"));
+        assert!(!suffix.matches("logs-KEEP"));
+        assert!(!suffix.matches("keep-logs"));
+        assert!(rule("-Keep", MatchMode::Suffix, true).matches("logs-KEEP"));
+    }
+
+    #[test]
+    fn glob_matches_whole_name() {
+        let sensitive = rule("prod-*-data", MatchMode::Glob, false);
+        assert!(sensitive.matches("prod-eu-data"));
+        assert!(!sensitive.matches("PROD-eu-data"));
+        assert!(!sensitive.matches("prod-eu-data-old"));
+
+        let insensitive = rule("prod-*-data", MatchMode::Glob, true);
+        assert!(insensitive.matches("PROD-eu-DATA"));
+    }
+
+    #[test]
+    fn regex_matches_anywhere_unless_anchored() {
+        let sensitive = rule("prod-[0-9]+", MatchMode::Regex, false);
+        assert!(sensitive.matches("old-prod-12-logs"));
+        assert!(!sensitive.matches("old-PROD-12-logs"));
+        assert!(!rule("^prod-[0-9]+$", MatchMode::Regex, false).matches("old-prod-12"));
+
+        let insensitive = rule("prod-[0-9]+", MatchMode::Regex, true);
+        assert!(insensitive.matches("old-PROD-12-logs"));
+    }
+
+    #[test]
+    fn only_contains_protects_names_around_the_pattern() {
+        assert!(ProtectionRule::contains("console").matches("console"));
+        assert!(ProtectionRule::contains("console").matches("reconsole-tmp"));
+
+        for narrower in [
+            rule("console", MatchMode::Exact, false),
+            rule("console", MatchMode::Prefix, false),
+            rule("console*", MatchMode::Glob, false),
+            rule("^console", MatchMode::Regex, false),
+        ] {
+            assert!(narrower.matches("console"), "{}", narrower);
+            assert!(!narrower.matches("reconsole-tmp"), "{}", narrower);
+        }
+    }
+
+    #[test]
+    fn literal_prefix_stops_at_the_first_wildcard() {
+        assert_eq!(
+            rule("ci-*-logs", MatchMode::Glob, false).literal_prefix(),
+            Some("ci-")
+        );
+        assert_eq!(
+            rule("ci-[ab]", MatchMode::Glob, false).literal_prefix(),
+            Some("ci-")
+        );
+        assert_eq!(
+            rule("ci-{a,b}", MatchMode::Glob, false).literal_prefix(),
+            Some("ci-")
+        );
+        assert_eq!(
+            rule("ci-logs", MatchMode::Glob, false).literal_prefix(),
+            Some("ci-logs")
+        );
+        assert_eq!(
+            rule("ci-", MatchMode::Prefix, false).literal_prefix(),
+            Some("ci-")
+        );
+        assert_eq!(
+            rule("ci", MatchMode::Exact, false).literal_prefix(),
+            Some("ci")
+        );
+        assert_eq!(
+            rule("*-logs", MatchMode::Glob, false).literal_prefix(),
+            None
+        );
+    }
+
+    #[test]
+    fn literal_prefix_needs_a_case_sensitive_anchored_rule() {
+        assert_eq!(rule("ci-*", MatchMode::Glob, true).literal_prefix(), None);
+        assert_eq!(rule("ci-", MatchMode::Prefix, true).literal_prefix(), None);
+        assert_eq!(rule("^ci-", MatchMode::Regex, false).literal_prefix(), None);
+        assert_eq!(
+            rule("ci-", MatchMode::Contains, false).literal_prefix(),
+            None
+        );
+        assert_eq!(rule("-ci", MatchMode::Suffix, false).literal_prefix(), None);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(ProtectionRule::new(RuleConfig {
+            pattern: "prod-[".to_owned(),
+            mode: MatchMode::Regex,
+            case_insensitive: false,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn tag_rule_without_value_matches_any_value() {
+        let rule = tag("do-not-delete", None, false);
+        assert!(rule.matches("do-not-delete", "true"));
+        assert!(rule.matches("do-not-delete", ""));
+        assert!(!rule.matches("Do-Not-Delete", "true"));
+        assert!(tag("Do-Not-Delete", None, true).matches("do-not-delete", "yes"));
+    }
+
+    #[test]
+    fn tag_rule_with_value_needs_both() {
+        let rule = tag("lifecycle", Some("permanent"), false);
+        assert!(rule.matches("lifecycle", "permanent"));
+        assert!(!rule.matches("lifecycle", "Permanent"));
+        assert!(!rule.matches("lifecycle", "temporary"));
+        assert!(tag("lifecycle", Some("permanent"), true).matches("Lifecycle", "Permanent"));
+    }
+}
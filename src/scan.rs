@@ -0,0 +1,286 @@
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use std::{collections::BTreeMap, fmt};
+
+use crate::{clients::Clients, metrics::StorageMetrics, DELETE_BATCH_SIZE};
+
+/// Everything s3-bang would have to remove from a bucket, gathered without deleting anything
+#[derive(Debug, Default)]
+pub struct BucketScan {
+    /// Every object version, current or not
+    pub versions: usize,
+    /// Versions that are the current version of their key
+    pub current_versions: usize,
+    pub delete_markers: usize,
+    pub multipart_uploads: usize,
+    pub total_bytes: u64,
+    pub bytes_by_storage_class: BTreeMap<String, u64>,
+    pub newest_last_modified: Option<DateTime>,
+    pub api_calls: ApiCalls,
+    /// Whether listing stopped at the page limit, so every count is a lower bound
+    pub sampled: bool,
+    /// CloudWatch's figures for the whole bucket, only fetched for sampled scans
+    pub estimate: Option<StorageMetrics>,
+}
+
+impl BucketScan {
+    pub fn noncurrent_versions(&self) -> usize {
+        self.versions - self.current_versions
+    }
+
+    /// Object versions and delete markers, which together count towards `max_objects`
+    pub fn objects(&self) -> usize {
+        self.versions + self.delete_markers
+    }
+}
+
+/// When a scan may stop before reaching the end of a bucket
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanLimits {
+    /// Pages of listings to read. Ignored for object versions while `max_objects` is set, since the object limit
+    /// needs every version counted
+    pub max_pages: Option<usize>,
+    /// Stop once the bucket holds more object versions and delete markers than this
+    pub max_objects: Option<usize>,
+}
+
+/// Requests `empty_bucket`, `abort_multipart_uploads` and `delete_bucket` would send
+#[derive(Debug, Default)]
+pub struct ApiCalls {
+    pub list_object_versions: usize,
+    pub delete_objects: usize,
+    pub list_multipart_uploads: usize,
+    pub abort_multipart_upload: usize,
+    pub delete_bucket: usize,
+}
+
+impl ApiCalls {
+    pub fn total(&self) -> usize {
+        self.list_object_versions
+            + self.delete_objects
+            + self.list_multipart_uploads
+            + self.abort_multipart_upload
+            + self.delete_bucket
+    }
+}
+
+impl fmt::Display for ApiCalls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ListObjectVersions, {} DeleteObjects, {} ListMultipartUploads, {} AbortMultipartUpload, {} DeleteBucket",
+            self.list_object_versions,
+            self.delete_objects,
+            self.list_multipart_uploads,
+            self.abort_multipart_upload,
+            self.delete_bucket
+        )
+    }
+}
+
+impl fmt::Display for BucketScan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.sampled {
+            writeln!(
+                f,
+                "\tScanning stopped early, so these figures and API calls are lower bounds"
+            )?;
+        }
+        writeln!(f, "\tObject versions:   {}", self.versions)?;
+        writeln!(f, "\t  current:         {}", self.current_versions)?;
+        writeln!(f, "\t  noncurrent:      {}", self.noncurrent_versions())?;
+        writeln!(f, "\tDelete markers:    {}", self.delete_markers)?;
+        writeln!(f, "\tMultipart uploads: {}", self.multipart_uploads)?;
+        writeln!(
+            f,
+            "\tTotal size:        {} ({} bytes)",
+            format_bytes(self.total_bytes),
+            self.total_bytes
+        )?;
+        for (class, bytes) in &self.bytes_by_storage_class {
+            writeln!(
+                f,
+                "\t  {:<16} {}",
+                format!("{}:", class),
+                format_bytes(*bytes)
+            )?;
+        }
+        write!(
+            f,
+            "\tAPI calls:         {} ({})",
+            self.api_calls.total(),
+            self.api_calls
+        )
+    }
+}
+
+pub async fn scan_bucket(clients: &Clients, name: &str) -> Result<BucketScan, aws_sdk_s3::Error> {
+    sample_bucket(clients, name, ScanLimits::default()).await
+}
+
+/// Scans a bucket until it ends or a limit is reached, marking the scan as sampled if it stopped early
+pub async fn sample_bucket(
+    clients: &Clients,
+    name: &str,
+    limits: ScanLimits,
+) -> Result<BucketScan, aws_sdk_s3::Error> {
+    let client = clients.for_bucket(name).await?;
+    let mut scan = BucketScan::default();
+    let mut key_marker: Option<String> = None;
+    let mut version_id_marker: Option<String> = None;
+
+    loop {
+        let objects = client
+            .list_object_versions()
+            .bucket(name)
+            .set_key_marker(key_marker.take())
+            .set_version_id_marker(version_id_marker.take())
+            .send()
+            .await?;
+        scan.api_calls.list_object_versions += 1;
+
+        let versions = objects.versions();
+        let delete_markers = objects.delete_markers();
+        scan.versions += versions.len();
+        scan.current_versions += versions
+            .iter()
+            .filter(|version| version.is_latest().unwrap_or_default())
+            .count();
+        scan.delete_markers += delete_markers.len();
+        for version in versions {
+            let size = version.size().unwrap_or_default().max(0) as u64;
+            let class = version
+                .storage_class()
+                .map(|class| class.as_str())
+                .unwrap_or("STANDARD");
+            scan.total_bytes += size;
+            *scan
+                .bytes_by_storage_class
+                .entry(class.to_owned())
+                .or_default() += size;
+        }
+        let last_modified = versions
+            .iter()
+            .filter_map(|version| version.last_modified())
+            .chain(
+                delete_markers
+                    .iter()
+                    .filter_map(|marker| marker.last_modified()),
+            );
+        for modified in last_modified {
+            if scan
+                .newest_last_modified
+                .is_none_or(|newest| is_newer(modified, &newest))
+            {
+                scan.newest_last_modified = Some(*modified);
+            }
+        }
+        scan.api_calls.delete_objects +=
+            (versions.len() + delete_markers.len()).div_ceil(DELETE_BATCH_SIZE);
+
+        if !objects.is_truncated().unwrap_or_default() {
+            break;
+        }
+        let out_of_pages = limits.max_objects.is_none()
+            && limits
+                .max_pages
+                .is_some_and(|max| scan.api_calls.list_object_versions >= max);
+        let over_limit = limits.max_objects.is_some_and(|max| scan.objects() > max);
+        if out_of_pages || over_limit {
+            scan.sampled = true;
+            break;
+        }
+
+        key_marker = objects.next_key_marker().map(str::to_owned);
+        version_id_marker = objects.next_version_id_marker().map(str::to_owned);
+    }
+
+    let mut key_marker: Option<String> = None;
+    let mut upload_id_marker: Option<String> = None;
+
+    loop {
+        let uploads = client
+            .list_multipart_uploads()
+            .bucket(name)
+            .set_key_marker(key_marker.take())
+            .set_upload_id_marker(upload_id_marker.take())
+            .send()
+            .await?;
+        scan.api_calls.list_multipart_uploads += 1;
+
+        let count = uploads.uploads().len();
+        scan.multipart_uploads += count;
+        scan.api_calls.abort_multipart_upload += count;
+
+        if !uploads.is_truncated().unwrap_or_default() {
+            break;
+        }
+        if limits
+            .max_pages
+            .is_some_and(|max| scan.api_calls.list_multipart_uploads >= max)
+        {
+            scan.sampled = true;
+            break;
+        }
+
+        key_marker = uploads.next_key_marker().map(str::to_owned);
+        upload_id_marker = uploads.next_upload_id_marker().map(str::to_owned);
+    }
+
+    scan.api_calls.delete_bucket = 1;
+    Ok(scan)
+}
+
+/// Scans each bucket in turn. With `max_objects` set, stops scanning once the buckets between them hold more
+/// object versions and delete markers than that, leaving the rest unscanned
+pub async fn scan_buckets(
+    clients: &Clients,
+    buckets: &[String],
+    max_pages: usize,
+    max_objects: Option<usize>,
+) -> Vec<(String, Result<BucketScan, aws_sdk_s3::Error>)> {
+    let mut scans = Vec::with_capacity(buckets.len());
+    let mut total = 0;
+
+    for bucket in buckets {
+        let limits = ScanLimits {
+            max_pages: Some(max_pages),
+            max_objects: max_objects.map(|max| max - total),
+        };
+        let scan = sample_bucket(clients, bucket, limits).await;
+        if let Ok(scan) = &scan {
+            total += scan.objects();
+        }
+        scans.push((bucket.to_owned(), scan));
+
+        if max_objects.is_some_and(|max| total > max) {
+            break;
+        }
+    }
+
+    scans
+}
+
+pub fn is_newer(a: &DateTime, b: &DateTime) -> bool {
+    (a.secs(), a.subsec_nanos()) > (b.secs(), b.subsec_nanos())
+}
+
+pub fn format_date(date: &DateTime) -> String {
+    date.fmt(DateTimeFormat::DateTime)
+        .unwrap_or_else(|_| date.secs().to_string())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}